@@ -1,15 +1,159 @@
 use crate::{
-    graph::{ItemState, ScrollUse},
-    scroll::Scroll,
+    goal::Goal,
+    graph::{ItemState, RandomStrategy, ScrollUse},
+    objective::{Exchange, Lexicographic, MaxP, MaxProfit, Objective},
+    reward::RewardTable,
+    scroll::{Event, ItemContext, MasterScroll, Scroll, ScrollEffect},
     stats::Stats,
 };
 use rustc_hash::FxHashMap;
 use std::{
+    borrow::Cow,
     cmp::Ordering,
     hash::{Hash, Hasher},
     rc::Rc,
 };
 
+/// Like other search functions in this program, this function assumes that
+/// `state` already has a well-defined value for `state.slots` and
+/// `state.stats`. Also, if `state.child.is_some()`, the value inside of
+/// `state.child` _will_ be ignored, and trampled/replaced. `scrolls` must be
+/// nonempty.
+///
+/// This function optimises for `objective`, which decides how items are
+/// valued once scrolling stops, and which scroll (if any) is chosen at each
+/// item state. The other search functions in this program (except for
+/// `solve_pareto`) are all built on top of this one, using the objectives
+/// provided by this crate, but any implementation of `Objective` will do.
+/// Costs in different currencies are totalled at the exchange rates of
+/// `objective` (see `Objective::rates` and `Exchange`).
+///
+/// If `state` has a (finite) budget, then the strategy will never spend more
+/// than that budget on scrolls, no matter how the scrolls turn out. See
+/// `ItemState::new_exists_with_budget`. The budget is the only thing that
+/// limits how many scrolls can be used on an item if any of `scrolls` can
+/// succeed without using up a slot (see `Success::slots`), or can add slots
+/// on a miss (see `Scroll::miss_slots`), so this function panics if there is
+/// such a scroll, but no budget.
+///
+/// If any of `scrolls` is a Clean Slate Scroll (see
+/// `ScrollEffect::CleanSlate`) or a reset scroll (see `ScrollEffect::Reset`),
+/// then an item can end up back in a state that it was in before, in which
+/// case the search is repeated until its results converge. Each repetition
+/// unrolls the resulting strategy tree a bit further, so the tree itself may
+/// not go around in circles for as long as the optimal strategy would. The
+/// same goes for scrolls that can add slots (see `Success::slots` and
+/// `Scroll::miss_slots`). The search is repeated at most a thousand times,
+/// after which its results are returned as they are, whether or not they
+/// have converged; this only happens when ending up back in an item state is
+/// very likely, e.g. when a Clean Slate Scroll almost always succeeds and a
+/// scroll almost always fails.
+///
+/// ## Returns:
+///
+/// - The child of `state`, i.e. the root `ScrollUse` of the strategy tree, or
+///   `None` if `state` is boomed.
+pub fn solve<'a, O: Objective + ?Sized>(
+    state: &mut ItemState<'a>,
+    scrolls: &'a [Scroll],
+    objective: &O,
+) -> Option<Rc<ScrollUse<'a>>> {
+    let master_scroll = Scroll::master_scroll(scrolls);
+    assert_bounded(state, &master_scroll);
+
+    sweep(
+        |memo| dfs(state, scrolls, &master_scroll, objective, memo),
+        scroll_uses_close,
+    )
+}
+
+/// Like `solve`, but for an item whose initial state is random, e.g. a clean
+/// item whose stats vary from one drop to the next. `states` pairs each
+/// possible initial state with its probability, and these probabilities must
+/// sum to one. Each initial state gets its own strategy tree, as its child,
+/// just as `solve` would give it, but all of the searches share the same
+/// cache, so an item state that can be reached from more than one initial
+/// state is only searched from once. `scrolls` must be nonempty. An item that
+/// can end up back in a state that it was in before is searched from just as
+/// `solve` would, including the limit on how many times the search repeats.
+///
+/// ## Returns:
+///
+/// - The strategy tree for each initial state, along with the expectation of
+///   their values (see `Objective::accumulate`) over the initial states.
+pub fn solve_random<'a, O: Objective + ?Sized>(
+    states: &mut [(f64, ItemState<'a>)],
+    scrolls: &'a [Scroll],
+    objective: &O,
+) -> RandomStrategy<'a> {
+    debug_assert!(
+        (states.iter().map(|(p_state, _)| p_state).sum::<f64>() - 1.0).abs()
+            < 1e-9
+    );
+
+    let master_scroll = Scroll::master_scroll(scrolls);
+    for (_, state) in states.iter() {
+        assert_bounded(state, &master_scroll);
+    }
+
+    let children = sweep(
+        |memo| {
+            states
+                .iter_mut()
+                .map(|(_, state)| {
+                    dfs(state, scrolls, &master_scroll, objective, memo)
+                })
+                .collect::<Vec<_>>()
+        },
+        scroll_uses_close,
+    );
+
+    // `Objective::accumulate` only knows how to add to a `ScrollUse`, so the
+    // expectation starts out as one, and its values are then moved out.
+    let mut expectation = ScrollUse::new_stop();
+    for ((p_state, _), child) in states.iter().zip(children.iter()) {
+        objective.accumulate(&mut expectation, *p_state, child.as_deref());
+    }
+
+    RandomStrategy {
+        p_goal: expectation.p_goal,
+        p_goals: expectation.p_goals,
+        exp_cost: expectation.exp_cost,
+        exp_costs: expectation.exp_costs,
+        exp_reward: expectation.exp_reward,
+        strategies: children,
+    }
+}
+
+/// Panics if `state` has no budget, but `master_scroll` can be used on it
+/// without using up a slot (see `MasterScroll::grants_slots`), as there would
+/// then be no end to the item states that could be searched from.
+fn assert_bounded(state: &ItemState<'_>, master_scroll: &MasterScroll) {
+    if let ItemState::Exists { budget, .. } = state {
+        assert!(
+            budget.is_finite() || !master_scroll.grants_slots,
+            "scrolls that don't use up slots can only be searched with a budget",
+        );
+    }
+}
+
+/// Returns `true` iff the values of `scroll_use` differ negligibly from those
+/// of `estimate`, for the purposes of `sweep`.
+fn scroll_uses_close(
+    scroll_use: &Rc<ScrollUse<'_>>,
+    estimate: &Rc<ScrollUse<'_>>,
+) -> bool {
+    close(scroll_use.p_goal, estimate.p_goal)
+        && close(scroll_use.exp_cost, estimate.exp_cost)
+        && close(scroll_use.exp_reward, estimate.exp_reward)
+        && scroll_use.p_goals.len() == estimate.p_goals.len()
+        && scroll_use
+            .p_goals
+            .iter()
+            .zip(estimate.p_goals.iter())
+            .all(|(p_goal, estimate)| close(*p_goal, *estimate))
+}
+
 /// Like other search functions in this program, this function assumes that
 /// `state` already has a well-defined value for `state.slots` and
 /// `state.stats`. Also, if `state.child.is_some()`, the value inside of
@@ -18,189 +162,1328 @@ use std::{
 ///
 /// This function optimises _only_ to maximise the probability of reaching
 /// `goal`, going with lower expected costs only when needed to break a tie.
+/// In other words, this is `solve` with the `MaxP` objective, at the exchange
+/// rates `rates` (see `Exchange`), which only matter for breaking ties.
+///
+/// If `state` has a (finite) budget, then the strategy will never spend more
+/// than that budget on scrolls, no matter how the scrolls turn out. See
+/// `ItemState::new_exists_with_budget`.
 pub fn solve_p<'a>(
     state: &mut ItemState<'a>,
     scrolls: &'a [Scroll],
-    goal: &Stats,
+    goal: &Goal,
+    rates: &[f64],
+) {
+    solve(state, scrolls, &Exchange::new(MaxP::new(goal), rates));
+}
+
+/// Like other search functions in this program, this function assumes that
+/// `state` already has a well-defined value for `state.slots` and
+/// `state.stats`. Unlike most of the other search functions, however, `state`
+/// is left untouched. `scrolls` must be nonempty.
+///
+/// This function compares the probability of reaching the goal (`p_goal`)
+/// with and without `event` (see `Event::apply`), optimising for `objective`
+/// either way, as `solve` would. With `MaxP` as the objective, this maximises
+/// the probability of reaching the goal, as `solve_p` would. To get the
+/// strategy tree for during the event, pass the result of `Event::apply` to
+/// `solve`.
+///
+/// ## Returns:
+///
+/// - The probability of reaching the goal without `event`.
+/// - The probability of reaching the goal during `event`.
+pub fn solve_event<O: Objective + ?Sized>(
+    state: &ItemState<'_>,
+    scrolls: &[Scroll],
+    objective: &O,
+    event: &Event,
+) -> (f64, f64) {
+    fn p_goal<O: Objective + ?Sized>(
+        state: &ItemState<'_>,
+        scrolls: &[Scroll],
+        objective: &O,
+    ) -> f64 {
+        solve(&mut state.clone(), scrolls, objective)
+            .map_or(0.0, |scroll_use| scroll_use.p_goal)
+    }
+
+    (
+        p_goal(state, scrolls, objective),
+        p_goal(state, &event.apply(scrolls), objective),
+    )
+}
+
+/// Like `solve_p`, but for an item whose initial state is random. `states`
+/// pairs each possible initial state with its probability, and these
+/// probabilities must sum to one. See `solve_random`. Costs in different
+/// currencies are totalled at `rates` apiece (see `Objective::rates`).
+///
+/// ## Returns:
+///
+/// - The probability of reaching `goal` from a random initial state.
+/// - The expected cost (due solely to scroll expenditure) of doing so.
+pub fn solve_p_random<'a>(
+    states: &mut [(f64, ItemState<'a>)],
+    scrolls: &'a [Scroll],
+    goal: &Goal,
+    rates: &[f64],
+) -> (f64, f64) {
+    let expectation =
+        solve_random(states, scrolls, &Exchange::new(MaxP::new(goal), rates));
+
+    (expectation.p_goal, expectation.exp_cost)
+}
+
+/// Like other search functions in this program, this function assumes that
+/// `state` already has a well-defined value for `state.slots` and
+/// `state.stats`. Also, if `state.child.is_some()`, the value inside of
+/// `state.child` _will_ be ignored, and trampled/replaced. `scrolls` must be
+/// nonempty, and so must `goals`.
+///
+/// `goals` are in order of decreasing priority. This function optimises to
+/// maximise the probability of reaching the first of `goals`, then to
+/// maximise the probability of reaching the second of `goals` only when
+/// needed to break a tie, and so on, going with lower expected costs only
+/// when needed to break a tie between all of `goals`. In other words, this is
+/// `solve` with the `Lexicographic` objective, at the exchange rates `rates`
+/// (see `Exchange`).
+///
+/// Within the resulting strategy tree, `p_goals` holds the probability of
+/// reaching each of `goals`.
+pub fn solve_lexicographic<'a>(
+    state: &mut ItemState<'a>,
+    scrolls: &'a [Scroll],
+    goals: &[Goal],
+    rates: &[f64],
 ) {
+    solve(
+        state,
+        scrolls,
+        &Exchange::new(Lexicographic::new(goals), rates),
+    );
+}
+
+/// Like other search functions in this program, this function assumes that
+/// `state` already has a well-defined value for `state.slots` and
+/// `state.stats`. Also, if `state.child.is_some()`, the value inside of
+/// `state.child` _will_ be ignored, and trampled/replaced. `scrolls` must be
+/// nonempty.
+///
+/// This function optimises to minimise the expected cost (due solely to
+/// scroll expenditure) per goal item reached, i.e. `exp_cost / p_goal`. This
+/// is the long-run average cost of a goal item, if we were to keep scrolling
+/// fresh copies of `state` using the same strategy until one of them reaches
+/// `goal`. Costs in different currencies are totalled at `rates` apiece (see
+/// `Objective::rates`).
+///
+/// This takes a few calls to `solve`, each of which is subject to its limit
+/// on how many times the search repeats, if an item can end up back in a
+/// state that it was in before.
+///
+/// ## Returns:
+///
+/// - The expected cost per goal item reached, assuming optimal scroll
+///   choices. This is positive infinity if `goal` cannot be reached at all.
+pub fn solve_cost<'a>(
+    state: &mut ItemState<'a>,
+    scrolls: &'a [Scroll],
+    goal: &Goal,
+    rates: &[f64],
+) -> f64 {
+    dinkelbach(state, scrolls, goal, 0.0, rates)
+}
+
+/// Like other search functions in this program, this function assumes that
+/// `state` already has a well-defined value for `state.slots` and
+/// `state.stats`. Also, if `state.child.is_some()`, the value inside of
+/// `state.child` _will_ be ignored, and trampled/replaced. `scrolls` must be
+/// nonempty.
+///
+/// This function assumes that `state` is a clean item that can be bought for
+/// `item_cost`. Whenever scrolling fails to reach `goal` (either because the
+/// item was boomed, or because it can no longer reach the goal), a fresh copy
+/// of `state` is bought, and scrolling starts over from the beginning. This
+/// function optimises to minimise the expected total cost (scrolls, plus
+/// replacement items) of obtaining an item that meets `goal`. Costs in
+/// different currencies are totalled at `rates` apiece (see
+/// `Objective::rates`), and `item_cost` is in the units of that total. Like
+/// `solve_cost`, this takes a few calls to `solve`.
+///
+/// ## Returns:
+///
+/// - The expected total cost of obtaining an item that meets `goal`, assuming
+///   optimal scroll choices. This includes the cost of any replacement items,
+///   but does not include the cost of `state` itself, which is assumed to
+///   already be in hand. This is positive infinity if `goal` cannot be reached
+///   at all.
+pub fn solve_renewal<'a>(
+    state: &mut ItemState<'a>,
+    scrolls: &'a [Scroll],
+    goal: &Goal,
+    item_cost: f64,
+    rates: &[f64],
+) -> f64 {
+    dinkelbach(state, scrolls, goal, item_cost, rates) - item_cost
+}
+
+/// Like other search functions in this program, this function assumes that
+/// `state` already has a well-defined value for `state.slots` and
+/// `state.stats`. Also, if `state.child.is_some()`, the value inside of
+/// `state.child` _will_ be ignored, and trampled/replaced. `scrolls` must be
+/// nonempty.
+///
+/// `value` is how much an item that meets `goal` is worth, in the same units
+/// as scroll costs, once costs in different currencies are totalled at
+/// `rates` apiece (see `Objective::rates`). This function optimises to
+/// maximise the expected profit, i.e. `value * p_goal - exp_cost`, going with
+/// higher probabilities of reaching `goal` only when needed to break a tie.
+/// As a result, scrolling stops as soon as continuing would be expected to
+/// lose money; if that's already the case for `state`, then the child of
+/// `state` is just `Action::Stop`. In other words, this is `solve` with the
+/// `MaxProfit` objective, at the exchange rates `rates` (see `Exchange`).
+///
+/// ## Returns:
+///
+/// - The expected profit, assuming optimal scroll choices. Note that this is
+///   never negative, as it's always possible to just not scroll at all.
+pub fn solve_profit<'a>(
+    state: &mut ItemState<'a>,
+    scrolls: &'a [Scroll],
+    goal: &Goal,
+    value: f64,
+    rates: &[f64],
+) -> f64 {
+    solve(
+        state,
+        scrolls,
+        &Exchange::new(MaxProfit::new(goal, value), rates),
+    )
+    .map_or(0.0, |scroll_use| {
+        scroll_use.exp_reward - scroll_use.exp_cost
+    })
+}
+
+/// Like other search functions in this program, this function assumes that
+/// `state` already has a well-defined value for `state.slots` and
+/// `state.stats`. Also, if `state.child.is_some()`, the value inside of
+/// `state.child` _will_ be ignored, and trampled/replaced. `scrolls` must be
+/// nonempty.
+///
+/// Rather than having a single goal, items are valued by `rewards`, based on
+/// their stats once scrolling stops. This function optimises to maximise the
+/// expected reward minus the expected cost, going with higher probabilities
+/// of earning any reward at all only when needed to break a tie. As with
+/// `solve_profit`, scrolling stops as soon as continuing would be expected to
+/// do more harm than good. In other words, this is `solve` with `rewards` as
+/// the objective, at the exchange rates `rates` (see `Exchange`), so rewards
+/// are in the units of the total cost at those rates.
+///
+/// Within the resulting strategy tree, `p_goal` is the probability of earning
+/// any (positive) reward at all, and `exp_reward` is the expected reward.
+///
+/// ## Returns:
+///
+/// - The expected reward minus the expected cost, assuming optimal scroll
+///   choices.
+pub fn solve_reward<'a>(
+    state: &mut ItemState<'a>,
+    scrolls: &'a [Scroll],
+    rewards: &RewardTable,
+    rates: &[f64],
+) -> f64 {
+    solve(state, scrolls, &Exchange::new(rewards, rates))
+        .map_or(0.0, |scroll_use| {
+            scroll_use.exp_reward - scroll_use.exp_cost
+        })
+}
+
+/// Like other search functions in this program, this function assumes that
+/// `state` already has a well-defined value for `state.slots` and
+/// `state.stats`. Unlike the other search functions, however, `state` is left
+/// untouched. `scrolls` must be nonempty.
+///
+/// Instead of a single optimal strategy, this function finds the [Pareto
+/// front](https://en.wikipedia.org/wiki/Pareto_front) of strategies, with
+/// respect to maximising the probability of reaching the goal and minimising
+/// the expected cost. In other words, it finds every strategy for which there
+/// is no other strategy that is at least as likely to reach the goal while
+/// also being strictly cheaper (or vice versa). Each such strategy has its
+/// own strategy tree.
+///
+/// `objective` decides whether or not an item has reached the goal once
+/// scrolling stops (as its `p_goal`, see `Objective::leaf`), which items
+/// aren't worth searching from at all, and what each currency is worth (see
+/// `Objective::rates`). Which of two strategies is better is up to the
+/// Pareto front, not to `objective`, so e.g. `MaxP` and `Exchange` of `MaxP`
+/// are what this function is usually given.
+///
+/// The exact Pareto front can easily contain tens of thousands of strategies,
+/// most of which differ only negligibly from their neighbours, so each front
+/// (including the fronts of every item state that is searched) is thinned
+/// out, such that no two of its strategies differ in probability of reaching
+/// the goal by less than `resolution`. The cheapest and the likeliest
+/// strategies are always kept. A `resolution` of `0.0` yields the exact
+/// Pareto front, but can take a very long time to compute.
+///
+/// If an item can end up back in a state that it was in before, then the
+/// search is repeated until its results converge, as with `solve`. Pareto
+/// fronts don't always converge, though, as they can flip between strategies
+/// that are equally good, so after a thousand repetitions, the last front is
+/// returned as it is.
+///
+/// ## Returns:
+///
+/// - The Pareto front, as the root `ScrollUse` of each strategy, ordered by
+///   increasing `p_goal` (and thus also by increasing `exp_cost`). The first
+///   element is always the cheapest possible strategy, which is usually just
+///   to stop. This is empty iff `state` is boomed.
+pub fn solve_pareto<'a, O: Objective + ?Sized>(
+    state: &ItemState<'a>,
+    scrolls: &'a [Scroll],
+    objective: &O,
+    resolution: f64,
+) -> Vec<Rc<ScrollUse<'a>>> {
     let master_scroll = Scroll::master_scroll(scrolls);
-    let mut cache = Default::default();
+    assert_bounded(state, &master_scroll);
+
+    sweep(
+        |memo| {
+            dfs_pareto(
+                state,
+                scrolls,
+                &master_scroll,
+                objective,
+                resolution,
+                memo,
+            )
+        },
+        |front, estimate| {
+            front.len() == estimate.len()
+                && front.iter().zip(estimate.iter()).all(
+                    |(scroll_use, estimate)| {
+                        close(scroll_use.p_goal, estimate.p_goal)
+                            && close(scroll_use.exp_cost, estimate.exp_cost)
+                    },
+                )
+        },
+    )
+    .map(|front| front.to_vec())
+    .unwrap_or_default()
+}
+
+/// Minimises `(item_cost + exp_cost) / p_goal`, which is the expected total
+/// cost of obtaining an item that meets `goal`, if each attempt starts by
+/// buying a fresh copy of `state` for `item_cost`. See `solve_cost` and
+/// `solve_renewal`.
+///
+/// This ratio cannot be optimised directly by a single search, so we use
+/// [Dinkelbach's
+/// method](https://en.wikipedia.org/wiki/Fractional_programming#Dinkelbach's_method):
+/// starting from the strategy that `solve_p` would choose, we repeatedly
+/// search for the strategy that maximises `lambda * p_goal - exp_cost`, where
+/// `lambda` is the ratio achieved by the previous strategy, until the ratio
+/// stops decreasing. Intuitively, `lambda` is what it costs to start over, so
+/// it's exactly what we stand to lose by failing to reach the goal.
+fn dinkelbach<'a>(
+    state: &mut ItemState<'a>,
+    scrolls: &'a [Scroll],
+    goal: &Goal,
+    item_cost: f64,
+    rates: &[f64],
+) -> f64 {
+    // Searches for the strategy that `solve_p` would choose if `lambda` is
+    // `None`, and otherwise for the strategy that `solve_profit` would choose
+    // if the goal were worth `lambda`, at the exchange rates `rates` either
+    // way. Returns `(p_goal, exp_cost)` of the resulting strategy.
+    let search = |state: &mut ItemState<'a>, lambda: Option<f64>| {
+        match lambda {
+            None => {
+                solve(state, scrolls, &Exchange::new(MaxP::new(goal), rates))
+            }
+            Some(lambda) => solve(
+                state,
+                scrolls,
+                &Exchange::new(MaxProfit::new(goal, lambda), rates),
+            ),
+        }
+        .map_or((0.0, 0.0), |scroll_use| {
+            (scroll_use.p_goal, scroll_use.exp_cost)
+        })
+    };
+
+    let mut lambda = None;
+    let (mut p_goal, mut exp_cost) = search(state, lambda);
+    if p_goal <= 0.0 {
+        return f64::INFINITY;
+    }
+
+    for _ in 0..DINKELBACH_MAX_ITERS {
+        let next_lambda = (item_cost + exp_cost) / p_goal;
+        let (next_p_goal, next_exp_cost) = search(state, Some(next_lambda));
+
+        if next_p_goal > 0.0
+            && (item_cost + next_exp_cost) / next_p_goal
+                < next_lambda * (1.0 - DINKELBACH_TOLERANCE)
+        {
+            lambda = Some(next_lambda);
+            p_goal = next_p_goal;
+            exp_cost = next_exp_cost;
+        } else {
+            // No (significant) improvement, so `lambda` already yields an
+            // optimal strategy. However, `state` now holds the strategy that
+            // we just found, which may be ever so slightly worse due to
+            // rounding, so we put the old one back.
+            search(state, lambda);
+
+            break;
+        }
+    }
 
-    dfs_p(state, scrolls, &master_scroll, goal, &mut cache);
+    (item_cost + exp_cost) / p_goal
 }
 
+/// The maximum number of iterations of Dinkelbach's method that `dinkelbach`
+/// will perform. In practice, it converges within a handful of iterations.
+const DINKELBACH_MAX_ITERS: usize = 64;
+
+/// The relative improvement in cost per goal item below which `dinkelbach`
+/// considers Dinkelbach's method to have converged.
+const DINKELBACH_TOLERANCE: f64 = 1e-12;
+
+/// Repeatedly calls `search`, which searches from the initial item state (or
+/// states) using the given `Memo`, until the results stop changing, and
+/// returns the result of the last call. `converged` decides whether or not a
+/// result for a given item state is close enough to the result for that
+/// state from the previous call.
+///
+/// Usually, there's only one call, because the search never comes across an
+/// item state that it's still in the middle of searching from, so its
+/// results are already exact. Otherwise, each call is a sweep of [value
+/// iteration](https://en.wikipedia.org/wiki/Markov_decision_process#Value_iteration),
+/// which starts out by assuming that scrolling stops at any such item state,
+/// and then uses the results of the previous sweep instead.
+///
+/// Value iteration can take a very long time to converge when ending up back
+/// in an item state is very likely, and it need not converge at all when the
+/// results are Pareto fronts, which can flip between equally good strategies
+/// from one sweep to the next. So there are at most `SWEEP_MAX_ITERS` calls,
+/// and the last one's results are returned even if they're still changing.
+/// In debug builds, that panics instead, as the results may be far from
+/// optimal.
+fn sweep<V, R>(
+    mut search: impl FnMut(&mut Memo<V>) -> R,
+    converged: impl Fn(&V, &V) -> bool,
+) -> R {
+    let mut memo = Memo {
+        cache: Default::default(),
+        estimates: Default::default(),
+        cyclic: false,
+    };
+
+    let settled = |memo: &Memo<V>| {
+        !memo.cyclic
+            || memo.cache.iter().all(|(key, (value, _))| {
+                memo.estimates
+                    .get(key)
+                    .is_some_and(|estimate| converged(value, estimate))
+            })
+    };
+
+    let mut result = search(&mut memo);
+    for _ in 1..SWEEP_MAX_ITERS {
+        if settled(&memo) {
+            return result;
+        }
+
+        memo.estimates = memo
+            .cache
+            .drain()
+            .map(|(key, (value, _))| (key, value))
+            .collect();
+        memo.cyclic = false;
+
+        result = search(&mut memo);
+    }
+
+    debug_assert!(
+        settled(&memo),
+        "value iteration didn't converge within {} sweeps",
+        SWEEP_MAX_ITERS,
+    );
+
+    result
+}
+
+/// Returns `true` iff `x` and `y` differ negligibly, for the purposes of
+/// `sweep`.
+fn close(x: f64, y: f64) -> bool {
+    x == y || (x - y).abs() <= SWEEP_TOLERANCE * x.abs().max(y.abs()).max(1.0)
+}
+
+/// The maximum number of sweeps that `sweep` will perform. Each sweep brings
+/// the results closer to the truth by roughly the probability of ending up
+/// back in an item state that's already been visited.
+const SWEEP_MAX_ITERS: usize = 1_000;
+
+/// The relative difference below which `sweep` considers two results to be
+/// the same.
+const SWEEP_TOLERANCE: f64 = 1e-12;
+
 /// Like other search functions in this program, this function assumes that
 /// `state` already has a well-defined value for `state.slots` and
 /// `state.stats`. Also, if `state.child.is_some()`, the value inside of
 /// `state.child` _will_ be ignored, and trampled/replaced. `scrolls` must be
 /// nonempty.
 ///
-/// This version of DFS optimises _only_ to maximise the probability of
-/// reaching `goal`, going with lower expected costs only when needed to break
-/// a tie.
+/// Which scroll is chosen at each item state is decided by `objective`.
+/// Stopping (i.e. `Action::Stop`) is always considered as an alternative to
+/// using any of the scrolls, and is the only choice when there are no slots
+/// left, so every `ItemState::Exists` that this function visits ends up with a
+/// child.
 ///
 /// The `master_scroll` parameter is used solely for optimisation, i.e. it's
 /// not _strictly_ necessary to have the correct master scroll for this
 /// function to behave correctly. However, **if you don't want to supply the
-/// correct master scroll, you should supply a null scroll** (any scroll that
-/// doesn't change the item on success) to preserve correctness. Likewise, the
-/// `cache` parameter is also used solely for [dynamic
+/// correct master scroll, you should supply one that is at least as extreme
+/// as the correct one** (in both its `best` and its `worst`) to preserve
+/// correctness. Likewise, the `memo` parameter is used for [dynamic
 /// programming](https://en.wikipedia.org/wiki/Dynamic_programming)
-/// optimisation. If no particular value for `cache` is appropriate, just pass
-/// in the empty map.
+/// optimisation, but also to deal with item states that lead back to
+/// themselves (see `sweep`). If no particular value for `memo` is
+/// appropriate, just pass in an empty one.
 ///
 /// ## Returns:
 ///
-/// - Probability of reaching `goal`, assuming optimal scroll choices after
-///   this point.
-/// - Expected cost after this point, again assuming optimal scroll choices
-///   after this point.
-fn dfs_p<'a, 'b>(
-    state: &'b mut ItemState<'a>,
+/// - The child of `state` (which holds the probability of reaching the goal,
+///   the expected cost, and the expected reward, assuming optimal scroll
+///   choices after this point), or `None` if `state` is boomed.
+fn dfs<'a, O: Objective + ?Sized>(
+    state: &mut ItemState<'a>,
     scrolls: &'a [Scroll],
-    master_scroll: &Scroll,
-    goal: &Stats,
-    cache: &mut FxHashMap<CacheKey, Rc<ScrollUse<'a>>>,
-) -> (f64, f64) {
+    master_scroll: &MasterScroll,
+    objective: &O,
+    memo: &mut Memo<Rc<ScrollUse<'a>>>,
+) -> Option<Rc<ScrollUse<'a>>> {
     debug_assert!(!scrolls.is_empty());
 
     match state {
         ItemState::Exists {
             slots,
             stats,
+            budget,
+            failed_slots,
+            hammers,
+            streak,
+            upgrades,
+            inventory,
+            purchases,
             child,
         } => {
+            let slots = Slots {
+                slots: *slots,
+                failed_slots: *failed_slots,
+                hammers: *hammers,
+                streak: *streak,
+                upgrades: *upgrades,
+            };
+
             // Check the cache for whether or not we have already optimised
             // from this starting point before. If so, we can return without
             // doing any real work.
-            if let Some(su) = cache.get(&CacheKey::new_borrowed(*slots, stats))
-            {
-                child.replace(Rc::clone(su));
+            if let Some(su) = memo.get(&CacheKey::new_borrowed(
+                slots, stats, inventory, purchases, *budget,
+            )) {
+                child.replace(Rc::clone(&su));
 
-                return (su.p_goal, su.exp_cost);
+                return Some(su);
             }
 
-            // Just in case `child.is_some()`.
-            let _ = child.take();
+            // Stopping here is always an option, so it's the first choice
+            // that every scroll gets compared against. This also trashes the
+            // old value of `child`, just in case `child.is_some()`.
+            let mut stop = ScrollUse::new_stop();
+            objective.leaf(stats, &mut stop);
+            let stop = Rc::new(stop);
+            child.replace(Rc::clone(&stop));
 
-            if slots == &0 {
-                return (if &*stats >= goal { 1.0 } else { 0.0 }, 0.0);
+            // No slots, failed slots, or hammers, so no scroll can be used,
+            // unless the item can be reset.
+            if (slots.slots, slots.failed_slots, slots.hammers) == (0, 0, 0)
+                && master_scroll.resets.is_empty()
+            {
+                return Some(stop);
             }
 
-            let slots_m1 = *slots - 1;
+            if master_scroll.cyclic() {
+                memo.start(
+                    CacheKey::new_owned(
+                        slots,
+                        stats.clone(),
+                        inventory.clone(),
+                        purchases.clone(),
+                        *budget,
+                    ),
+                    || Rc::clone(&stop),
+                );
+            }
 
             for scroll in scrolls {
-                let mut scroll_use = ScrollUse::new(scroll);
-
-                if scroll.p_suc > 0.0 {
-                    // New stats of the item, assuming a success of this
-                    // scroll.
-                    let outcome_suc_stats = stats.plus(&scroll.stats);
-
-                    // Is it even possible to reach the goal at this point?
-                    // This is the "master scroll" heuristic.
-                    match (outcome_suc_stats.plus(
-                        &(master_scroll.stats.clone() * u16::from(slots_m1)),
-                    ))
-                    .partial_cmp(goal)
-                    {
-                        Some(Ordering::Less) | None => continue,
-                        _ => (),
-                    }
+                let mut scroll_use = ScrollUse::new(scroll, objective.rates());
 
-                    let outcome_suc = scroll_use.push_outcome(
-                        ItemState::new_exists(slots_m1, outcome_suc_stats),
-                    );
-
-                    // (probability of reaching the goal conditioned on this
-                    // scroll succeeding, expected cost after this point
-                    // conditioned on this scroll succeeding)
-                    let (p_goal_cond_suc, exp_cost_cond_suc) = dfs_p(
-                        outcome_suc,
-                        scrolls,
+                let (exp_uses, outcomes) = if let Some(outcomes) =
+                    scroll_outcomes(
+                        slots,
+                        stats,
+                        Funds {
+                            inventory,
+                            purchases,
+                            budget: *budget,
+                        },
+                        scroll,
                         master_scroll,
-                        goal,
-                        cache,
-                    );
-                    scroll_use.p_goal += scroll.p_suc * p_goal_cond_suc;
-                    scroll_use.exp_cost += scroll.p_suc * exp_cost_cond_suc;
-                }
-
-                // Is it even possible to reach the goal, assuming that this
-                // scroll fails? This is the "master scroll" heuristic.
-                let goal_possible_cond_fail = &(stats.plus(
-                    &(master_scroll.stats.clone() * u16::from(slots_m1)),
-                )) >= goal;
+                        objective,
+                    ) {
+                    outcomes
+                } else {
+                    continue;
+                };
+                let (exp_cost, exp_costs) =
+                    exp_costs(scroll, purchases, objective.rates(), exp_uses);
+                scroll_use.exp_cost = exp_cost;
+                scroll_use.exp_costs = exp_costs;
 
-                if goal_possible_cond_fail && scroll.p_suc < 1.0 {
-                    let outcome_fail = scroll_use.push_outcome(
-                        ItemState::new_exists(slots_m1, stats.clone()),
-                    );
+                for (p_outcome, outcome) in outcomes {
+                    let outcome = scroll_use.push_outcome(p_outcome, outcome);
 
-                    // (probability of reaching the goal conditioned on this
-                    // scroll failing, expected cost after this point
-                    // conditioned on this scroll failing)
-                    let (p_goal_cond_fail, exp_cost_cond_fail) = dfs_p(
-                        outcome_fail,
-                        scrolls,
-                        master_scroll,
-                        goal,
-                        cache,
+                    // The choice made after this outcome, which holds the
+                    // values conditioned on this outcome.
+                    let outcome_scroll_use =
+                        dfs(outcome, scrolls, master_scroll, objective, memo);
+                    objective.accumulate(
+                        &mut scroll_use,
+                        p_outcome,
+                        outcome_scroll_use.as_deref(),
                     );
-                    let p_fail = if scroll.dark {
-                        (1.0 - scroll.p_suc) / 2.0
-                    } else {
-                        1.0 - scroll.p_suc
-                    };
-                    scroll_use.p_goal += p_fail * p_goal_cond_fail;
-                    scroll_use.exp_cost += p_fail * exp_cost_cond_fail;
-
-                    if scroll.dark {
-                        let outcome_boom =
-                            scroll_use.push_outcome(ItemState::new_boomed());
-
-                        // These results are always `(0.0, 0.0)`, so we ignore
-                        // them.
-                        let (_p_goal_cond_boom, _exp_cost_cond_boom) = dfs_p(
-                            outcome_boom,
-                            scrolls,
-                            master_scroll,
-                            goal,
-                            cache,
-                        );
-                    }
                 }
 
                 // Now, we check whether or not using this scroll is a better
-                // choice than using any of the scrolls that we tested
-                // previously.
+                // choice than stopping, or than using any of the scrolls that
+                // we tested previously.
                 if let Some(child_scroll_use) = child {
-                    // We use the expected cost to break ties here.
-                    if scroll_use.p_goal > child_scroll_use.p_goal
-                        || (scroll_use.p_goal >= child_scroll_use.p_goal
-                            && scroll_use.exp_cost < child_scroll_use.exp_cost)
-                    {
+                    if objective.prefers(&scroll_use, child_scroll_use) {
                         child.replace(Rc::new(scroll_use));
                     }
-                } else {
-                    child.replace(Rc::new(scroll_use));
                 }
             }
 
-            if let Some(child_scroll_use) = child.as_ref() {
-                cache.insert(
-                    CacheKey::new_owned(*slots, stats.clone()),
-                    Rc::clone(child_scroll_use),
+            let child_scroll_use =
+                child.as_ref().unwrap_or_else(|| unreachable!());
+            memo.finish(
+                CacheKey::new_owned(
+                    slots,
+                    stats.clone(),
+                    inventory.clone(),
+                    purchases.clone(),
+                    *budget,
+                ),
+                Rc::clone(child_scroll_use),
+            );
+
+            Some(Rc::clone(child_scroll_use))
+        }
+        ItemState::Boomed => None,
+    }
+}
+
+/// The Pareto-front-finding analogue of `dfs`. Unlike `dfs`, this function
+/// leaves `state` untouched, because there is no single child to attach to it.
+/// The `master_scroll` and `memo` parameters are used just like they are used
+/// in `dfs`, and `resolution` is used just like it is used in
+/// `solve_pareto`. `objective` is only used to value items once scrolling
+/// stops, to decide which outcomes aren't worth exploring, and for its
+/// exchange rates.
+///
+/// Every possible way of combining the Pareto fronts of the outcomes of a
+/// given scroll is considered, and the dominated combinations are discarded
+/// as we go along. Then, the fronts resulting from each scroll (and from
+/// stopping) are merged into a single front.
+///
+/// ## Returns:
+///
+/// - The Pareto front, in the same form as is returned by `solve_pareto`, or
+///   `None` if `state` is boomed.
+fn dfs_pareto<'a, O: Objective + ?Sized>(
+    state: &ItemState<'a>,
+    scrolls: &'a [Scroll],
+    master_scroll: &MasterScroll,
+    objective: &O,
+    resolution: f64,
+    memo: &mut Memo<Rc<[Rc<ScrollUse<'a>>]>>,
+) -> Option<Rc<[Rc<ScrollUse<'a>>]>> {
+    debug_assert!(!scrolls.is_empty());
+
+    let (slots, stats, inventory, purchases, budget) = match state {
+        ItemState::Exists {
+            slots,
+            stats,
+            budget,
+            failed_slots,
+            hammers,
+            streak,
+            upgrades,
+            inventory,
+            purchases,
+            ..
+        } => (
+            Slots {
+                slots: *slots,
+                failed_slots: *failed_slots,
+                hammers: *hammers,
+                streak: *streak,
+                upgrades: *upgrades,
+            },
+            stats,
+            inventory,
+            purchases,
+            *budget,
+        ),
+        ItemState::Boomed => return None,
+    };
+
+    if let Some(front) = memo.get(&CacheKey::new_borrowed(
+        slots, stats, inventory, purchases, budget,
+    )) {
+        return Some(front);
+    }
+
+    let mut stop = ScrollUse::new_stop();
+    objective.leaf(stats, &mut stop);
+    let stop = Rc::new(stop);
+
+    if master_scroll.cyclic() {
+        memo.start(
+            CacheKey::new_owned(
+                slots,
+                stats.clone(),
+                inventory.clone(),
+                purchases.clone(),
+                budget,
+            ),
+            || vec![Rc::clone(&stop)].into(),
+        );
+    }
+
+    let mut front = vec![stop];
+
+    for scroll in scrolls {
+        let (exp_uses, outcomes) = if let Some(outcomes) = scroll_outcomes(
+            slots,
+            stats,
+            Funds {
+                inventory,
+                purchases,
+                budget,
+            },
+            scroll,
+            master_scroll,
+            objective,
+        ) {
+            outcomes
+        } else {
+            continue;
+        };
+
+        // The Pareto front of each outcome, or `None` if the outcome is that
+        // the item is boomed.
+        let outcome_fronts: Vec<_> = outcomes
+            .iter()
+            .map(|(_, outcome)| {
+                dfs_pareto(
+                    outcome,
+                    scrolls,
+                    master_scroll,
+                    objective,
+                    resolution,
+                    memo,
+                )
+            })
+            .collect();
+
+        let (exp_cost, exp_costs) =
+            exp_costs(scroll, purchases, objective.rates(), exp_uses);
+
+        // Each combination is (probability of reaching the goal, expected
+        // cost, index into each outcome's front).
+        let mut combos = vec![(0.0, exp_cost, Vec::new())];
+        for ((p_outcome, _), outcome_front) in
+            outcomes.iter().zip(outcome_fronts.iter())
+        {
+            let outcome_front = if let Some(outcome_front) = outcome_front {
+                outcome_front
+            } else {
+                for (_, _, choices) in combos.iter_mut() {
+                    choices.push(0);
+                }
+
+                continue;
+            };
+
+            combos = pareto_front(
+                combos
+                    .iter()
+                    .flat_map(|(p_goal, exp_cost, choices)| {
+                        outcome_front.iter().enumerate().map(
+                            move |(i, outcome_scroll_use)| {
+                                let mut choices = choices.clone();
+                                choices.push(i);
+
+                                (
+                                    p_goal
+                                        + p_outcome
+                                            * outcome_scroll_use.p_goal,
+                                    exp_cost
+                                        + p_outcome
+                                            * outcome_scroll_use.exp_cost,
+                                    choices,
+                                )
+                            },
+                        )
+                    })
+                    .collect(),
+                |(p_goal, exp_cost, _)| (*p_goal, *exp_cost),
+                resolution,
+            );
+        }
+
+        for (_, _, choices) in combos {
+            let mut scroll_use = ScrollUse::new(scroll, objective.rates());
+            scroll_use.exp_cost = exp_cost;
+            scroll_use.exp_costs = exp_costs.clone();
+
+            for (((p_outcome, outcome), outcome_front), choice) in
+                outcomes.iter().zip(outcome_fronts.iter()).zip(choices)
+            {
+                let outcome =
+                    scroll_use.push_outcome(*p_outcome, outcome.clone());
+
+                // The choice made after this outcome, which holds the values
+                // conditioned on this outcome.
+                let outcome_scroll_use = outcome_front
+                    .as_ref()
+                    .map(|outcome_front| Rc::clone(&outcome_front[choice]));
+                if let (
+                    ItemState::Exists { child, .. },
+                    Some(outcome_scroll_use),
+                ) = (outcome, &outcome_scroll_use)
+                {
+                    child.replace(Rc::clone(outcome_scroll_use));
+                }
+                objective.accumulate(
+                    &mut scroll_use,
+                    *p_outcome,
+                    outcome_scroll_use.as_deref(),
                 );
+            }
+
+            front.push(Rc::new(scroll_use));
+        }
+    }
+
+    let front: Rc<[_]> = pareto_front(
+        front,
+        |scroll_use| (scroll_use.p_goal, scroll_use.exp_cost),
+        resolution,
+    )
+    .into();
+    memo.finish(
+        CacheKey::new_owned(
+            slots,
+            stats.clone(),
+            inventory.clone(),
+            purchases.clone(),
+            budget,
+        ),
+        Rc::clone(&front),
+    );
+
+    Some(front)
+}
+
+/// Filters `points` down to just those that are Pareto-optimal, with respect
+/// to maximising the probability of reaching the goal and minimising the
+/// expected cost. `key` extracts these two values (in that order) from a
+/// point. Of any points that are tied in both respects, only one is kept.
+///
+/// The Pareto-optimal points are then thinned out, such that no two of them
+/// differ in probability of reaching the goal by less than `resolution`,
+/// except that the first and last points are always kept.
+///
+/// ## Returns:
+///
+/// - The remaining points, ordered by increasing probability of reaching the
+///   goal (and thus also by increasing expected cost).
+fn pareto_front<T>(
+    mut points: Vec<T>,
+    key: impl Fn(&T) -> (f64, f64),
+    resolution: f64,
+) -> Vec<T> {
+    points.sort_by(|a, b| {
+        let (p_goal_a, exp_cost_a) = key(a);
+        let (p_goal_b, exp_cost_b) = key(b);
+
+        p_goal_b
+            .partial_cmp(&p_goal_a)
+            .unwrap_or(Ordering::Equal)
+            .then_with(|| {
+                exp_cost_a
+                    .partial_cmp(&exp_cost_b)
+                    .unwrap_or(Ordering::Equal)
+            })
+    });
+
+    // Going from most to least likely to reach the goal, a point is only
+    // Pareto-optimal if it's strictly cheaper than all of the points before
+    // it.
+    let mut min_exp_cost = f64::INFINITY;
+    points.retain(|point| {
+        let (_, exp_cost) = key(point);
+
+        if exp_cost < min_exp_cost {
+            min_exp_cost = exp_cost;
+
+            true
+        } else {
+            false
+        }
+    });
+    points.reverse();
+
+    // Now, going from least to most likely to reach the goal, we skip any
+    // point that isn't sufficiently more likely to reach the goal than the
+    // last point that we kept.
+    let len = points.len();
+    let mut last_p_goal = f64::NEG_INFINITY;
+    let mut i = 0;
+    points.retain(|point| {
+        let (p_goal, _) = key(point);
+        i += 1;
+
+        if p_goal >= last_p_goal + resolution || i == len {
+            last_p_goal = p_goal;
+
+            true
+        } else {
+            false
+        }
+    });
+
+    points
+}
+
+/// Returns all possible outcomes of using `scroll` on an item that has `slots`
+/// (see `Slots`), `stats` stats, and `funds` (see `Funds`), each paired with
+/// the probability of that outcome occurring.
+///
+/// If `scroll` costs more than the budget, or there are none of it left in
+/// the inventory, or there are no slots (or failed slots, or hammers) for it
+/// to be used on, then it cannot be used at all, so `None` is returned.
+///
+/// The probabilities of `scroll` succeeding, booming the item, and missing
+/// are as given by `Scroll::probabilities` for this item. A miss adds to the
+/// item's streak of misses, if the search is keeping track of it (see
+/// `MasterScroll::streaks`), and a success ends it. Likewise, a success of a
+/// regular scroll adds to the item's upgrades, and a reset undoes them.
+///
+/// If a miss of `scroll` would leave the item exactly as it was (e.g. because
+/// `scroll.keeps_slot`, and there is no budget), then the same decision would
+/// just be made again, so `scroll` is instead used over and over until it
+/// doesn't miss. That outcome is left out, and the probabilities of the
+/// other outcomes are conditioned on it not happening.
+///
+/// Each possible change to stats on success (see `Scroll::success`) is its
+/// own outcome. Outcomes from which the "master scroll" heuristic (see `dfs`)
+/// says that nothing is reachable (see `Objective::reachable`) are left out,
+/// as there is nothing to gain from exploring them. If not even a success of
+/// `scroll` can reach anything, then `None` is returned, as there is nothing
+/// to gain from using `scroll` at all.
+///
+/// ## Returns:
+///
+/// - The expected number of times that `scroll` is used, which is one unless
+///   `scroll` is used over and over as described above.
+/// - The outcomes, each paired with its probability.
+fn scroll_outcomes<'a, O: Objective + ?Sized>(
+    slots: Slots,
+    stats: &Stats,
+    funds: Funds<'_>,
+    scroll: &Scroll,
+    master_scroll: &MasterScroll,
+    objective: &O,
+) -> Option<(f64, Vec<(f64, ItemState<'a>)>)> {
+    let Funds {
+        inventory,
+        purchases,
+        budget,
+    } = funds;
+    let cost = scroll.cost_given(purchases, objective.rates());
+    if cost > budget {
+        return None;
+    }
+
+    // The inventory that the item is left with after using `scroll`.
+    let inventory_m_scroll = if let Some(i) = scroll.inventory {
+        if inventory.get(i).copied().unwrap_or_default() == 0 {
+            return None;
+        }
 
-                (child_scroll_use.p_goal, child_scroll_use.exp_cost)
+        let mut inventory = inventory.to_vec();
+        inventory[i] -= 1;
+
+        inventory
+    } else {
+        inventory.to_vec()
+    };
+
+    // The purchases that the item is left with after using `scroll`. Once
+    // every tier has been bought up, there's no need to count any further.
+    let mut purchases_m_scroll = purchases.to_vec();
+    if let Some(pricing) = &scroll.pricing {
+        let i = pricing.purchases;
+        let bought = purchases.get(i).copied().unwrap_or_default();
+        if bought < pricing.tiered() {
+            if purchases_m_scroll.len() <= i {
+                purchases_m_scroll.resize(i + 1, 0);
+            }
+            purchases_m_scroll[i] = bought + 1;
+        }
+    }
+
+    let (p_suc, p_boom, p_miss) = scroll.probabilities(&slots.context(stats));
+
+    // The slots that the item is left with on a miss.
+    let mut slots_fail = match scroll.effect {
+        ScrollEffect::Regular => {
+            if slots.slots < scroll.slots_needed() {
+                return None;
+            }
+
+            if scroll.keeps_slot {
+                slots
             } else {
-                // This branch is possibly taken when our "master scroll"
-                // heuristic rejects all scrolls.
-                (0.0, 0.0)
+                slots.plus(scroll.miss_slots)
+            }
+        }
+        ScrollEffect::CleanSlate => {
+            if slots.failed_slots == 0 {
+                return None;
+            }
+
+            slots
+        }
+        ScrollEffect::Hammer => {
+            if slots.hammers == 0 {
+                return None;
+            }
+
+            slots
+        }
+        ScrollEffect::Reset { .. } => slots,
+    };
+    // Slots lost to failure are only worth keeping track of if they can be
+    // recovered, or if they can change the probability of success (see
+    // `ItemContext`); otherwise, they would only make for needless cache
+    // misses.
+    if !master_scroll.clean_slate && !master_scroll.streaks {
+        slots_fail.failed_slots = slots.failed_slots;
+    }
+    if master_scroll.streaks {
+        slots_fail.streak = slots_fail.streak.saturating_add(1);
+    }
+    let budget_m_cost = budget - cost;
+    let mut outcomes = Vec::with_capacity(scroll.success.len() + 2);
+
+    if p_suc > 0.0 {
+        let mut push_success =
+            |p_outcome, outcome_suc_stats: Stats, outcome_suc_slots: Slots| {
+                // A success ends any streak of misses.
+                let outcome_suc_slots = Slots {
+                    streak: 0,
+                    ..outcome_suc_slots
+                };
+
+                // Is it even possible to reach anything at this point? This
+                // is the "master scroll" heuristic.
+                let (lo, hi) = outcome_suc_slots
+                    .bounds(master_scroll, &outcome_suc_stats);
+                if objective.reachable(&lo, &hi) {
+                    outcomes.push((
+                        p_outcome,
+                        outcome_suc_slots.item_state(
+                            outcome_suc_stats,
+                            inventory_m_scroll.clone(),
+                            purchases_m_scroll.clone(),
+                            budget_m_cost,
+                        ),
+                    ));
+                }
+            };
+
+        match &scroll.effect {
+            ScrollEffect::Regular => {
+                for (p_success, success) in scroll.success.iter() {
+                    // New stats and slots of the item, assuming this
+                    // particular success of this scroll. Slots used up by a
+                    // success aren't lost to failure, so they can't be
+                    // recovered.
+                    let mut slots_suc = slots.plus(success.slots);
+                    slots_suc.failed_slots = slots.failed_slots;
+                    if master_scroll.streaks {
+                        slots_suc.upgrades = slots.upgrades.saturating_add(1);
+                    }
+
+                    push_success(
+                        p_suc * p_success,
+                        stats.plus_delta(&success.stats),
+                        slots_suc,
+                    );
+                }
+            }
+            ScrollEffect::CleanSlate => push_success(
+                p_suc,
+                stats.clone(),
+                Slots {
+                    slots: slots.slots.saturating_add(1),
+                    failed_slots: slots.failed_slots - 1,
+                    ..slots
+                },
+            ),
+            ScrollEffect::Hammer => push_success(
+                p_suc,
+                stats.clone(),
+                Slots {
+                    slots: slots.slots.saturating_add(1),
+                    hammers: slots.hammers - 1,
+                    ..slots
+                },
+            ),
+            ScrollEffect::Reset {
+                slots: clean_slots,
+                stats: clean_stats,
+            } => push_success(
+                p_suc,
+                clean_stats.clone(),
+                Slots {
+                    slots: *clean_slots,
+                    failed_slots: 0,
+                    upgrades: 0,
+                    ..slots
+                },
+            ),
+        }
+
+        if outcomes.is_empty() {
+            return None;
+        }
+    }
+
+    // Is it even possible to reach anything, assuming that this scroll misses?
+    // This is the "master scroll" heuristic.
+    let (lo, hi) = slots_fail.bounds(master_scroll, stats);
+    let goal_possible_cond_fail = objective.reachable(&lo, &hi);
+
+    let mut exp_uses = 1.0;
+    if goal_possible_cond_fail {
+        if slots_fail == slots
+            && inventory_m_scroll == inventory
+            && purchases_m_scroll == purchases
+            && budget_m_cost.to_bits() == budget.to_bits()
+        {
+            // A miss leaves the item exactly as it was, so we just keep using
+            // the scroll until it doesn't miss.
+            if p_miss >= 1.0 {
+                return None;
             }
+
+            exp_uses = 1.0 / (1.0 - p_miss);
+        } else if p_miss > 0.0 {
+            outcomes.push((
+                p_miss,
+                slots_fail.item_state(
+                    stats.clone(),
+                    inventory_m_scroll.clone(),
+                    purchases_m_scroll.clone(),
+                    budget_m_cost,
+                ),
+            ));
+        }
+
+        if p_boom > 0.0 {
+            outcomes.push((p_boom, ItemState::new_boomed()));
+        }
+    }
+
+    for (p_outcome, _) in outcomes.iter_mut() {
+        *p_outcome *= exp_uses;
+    }
+
+    Some((exp_uses, outcomes))
+}
+
+/// Returns the expected cost of using `scroll` `exp_uses` times (see
+/// `scroll_outcomes`) on an item for which `purchases` have been bought (see
+/// `Scroll::pricing`), both in total, at `rates` apiece (see
+/// `Objective::rates`), and in each currency (see `Scroll::costs`).
+fn exp_costs(
+    scroll: &Scroll,
+    purchases: &[u8],
+    rates: &[f64],
+    exp_uses: f64,
+) -> (f64, Vec<f64>) {
+    (
+        exp_uses * scroll.cost_given(purchases, rates),
+        scroll
+            .costs_given(purchases)
+            .into_iter()
+            .map(|cost| exp_uses * cost)
+            .collect(),
+    )
+}
+
+/// What an item has to spend on scrolls (see `ItemState`): the budget, how
+/// many are left of each scroll that there are only so many of (see
+/// `Scroll::inventory`), and how many have been bought of each scroll whose
+/// price depends on that (see `Scroll::pricing`).
+#[derive(Clone, Copy)]
+struct Funds<'f> {
+    inventory: &'f [u8],
+    purchases: &'f [u8],
+    budget: f64,
+}
+
+/// The slots of an item (see `ItemState`), along with the slots that it has
+/// lost to failed scrolls, the hammers that it has left, how many scrolls in a
+/// row have missed, and how many upgrades it has had.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+struct Slots {
+    slots: u8,
+    failed_slots: u8,
+    hammers: u8,
+    streak: u8,
+    upgrades: u8,
+}
+
+impl Slots {
+    /// Returns these slots, after `delta` slots are added (or, if `delta` is
+    /// negative, used up). Any slots that are used up are lost to failure.
+    fn plus(self, delta: i8) -> Self {
+        let slots = (i16::from(self.slots) + i16::from(delta))
+            .clamp(0, i16::from(u8::MAX)) as u8;
+
+        Self {
+            slots,
+            failed_slots: self
+                .failed_slots
+                .saturating_add(self.slots.saturating_sub(slots)),
+            ..self
+        }
+    }
+
+    /// Creates a new `ItemState::Exists` that has these slots, with stats
+    /// `stats`, inventory `inventory`, purchases `purchases`, and `budget`
+    /// left to spend.
+    fn item_state<'a>(
+        self,
+        stats: Stats,
+        inventory: Vec<u8>,
+        purchases: Vec<u8>,
+        budget: f64,
+    ) -> ItemState<'a> {
+        ItemState::Exists {
+            slots: self.slots,
+            stats,
+            budget,
+            failed_slots: self.failed_slots,
+            hammers: self.hammers,
+            streak: self.streak,
+            upgrades: self.upgrades,
+            inventory,
+            purchases,
+            child: None,
+        }
+    }
+
+    /// Returns what a `SuccessRate` needs to know about an item that has these
+    /// slots and stats `stats`.
+    fn context(self, stats: &Stats) -> ItemContext<'_> {
+        ItemContext {
+            slots: self.slots,
+            failed_slots: self.failed_slots,
+            hammers: self.hammers,
+            streak: self.streak,
+            upgrades: self.upgrades,
+            stats,
         }
-        ItemState::Boomed => (0.0, 0.0),
+    }
+
+    /// Returns the least and greatest stats (respectively) that an item that
+    /// has these slots and stats `stats` could possibly end up with. See
+    /// `MasterScroll::bounds`.
+    fn bounds(
+        self,
+        master_scroll: &MasterScroll,
+        stats: &Stats,
+    ) -> (Stats, Stats) {
+        master_scroll.bounds(
+            stats,
+            self.slots,
+            self.failed_slots,
+            self.hammers,
+        )
+    }
+}
+
+/// What `dfs` remembers about the item states that it has come across during a
+/// single sweep (see `sweep`), where `V` is the result for an item state. The
+/// same goes for `dfs_pareto`.
+struct Memo<V> {
+    /// The result for each item state that the search has come across during
+    /// this sweep. If the search is still in the middle of searching from an
+    /// item state, then its result is only a placeholder, and is marked as
+    /// such (`true`).
+    cache: FxHashMap<CacheKey<'static>, (V, bool)>,
+    /// The result for each item state from the previous sweep, if any.
+    estimates: FxHashMap<CacheKey<'static>, V>,
+    /// Did the search come across a placeholder during this sweep? If so, then
+    /// the results of this sweep are only estimates.
+    cyclic: bool,
+}
+
+impl<V: Clone> Memo<V> {
+    /// Returns the result for the item state `key`, if the search has already
+    /// come across it during this sweep.
+    fn get(&mut self, key: &CacheKey<'_>) -> Option<V> {
+        let (value, placeholder) = self.cache.get(key)?;
+        self.cyclic |= *placeholder;
+
+        Some(value.clone())
+    }
+
+    /// Notes that the search is starting to search from the item state `key`.
+    /// Until it's done, coming across `key` again yields a placeholder: the
+    /// result from the previous sweep, or else the result of just stopping
+    /// (`stop`). This is only needed if an item state can lead back to
+    /// itself.
+    fn start(&mut self, key: CacheKey<'static>, stop: impl FnOnce() -> V) {
+        let placeholder =
+            self.estimates.get(&key).cloned().unwrap_or_else(stop);
+        self.cache.insert(key, (placeholder, true));
+    }
+
+    /// Notes that the search is done searching from the item state `key`, and
+    /// that `value` is the result.
+    fn finish(&mut self, key: CacheKey<'static>, value: V) {
+        self.cache.insert(key, (value, false));
     }
 }
 
@@ -235,30 +1518,61 @@ impl<'sh> Hash for StatsHandle<'sh> {
 
 /// This type exists specifically to avoid calling `Vec::clone` every time that
 /// we do a lookup in the cache.
+///
+/// The budget is compared and hashed bitwise. Two budgets that are left after
+/// spending the same on scrolls, but in a different order, can differ in
+/// their last bits (e.g. `(1.0 - 0.1) - 0.2 != (1.0 - 0.2) - 0.1`), in which
+/// case they get separate cache entries, and the search from the one
+/// duplicates the work of the search from the other.
 struct CacheKey<'sh> {
-    slots: u8,
+    slots: Slots,
     stats: StatsHandle<'sh>,
+    inventory: Cow<'sh, [u8]>,
+    purchases: Cow<'sh, [u8]>,
+    budget: f64,
 }
 
 impl<'sh> CacheKey<'sh> {
-    fn new_owned(slots: u8, stats: Stats) -> Self {
+    fn new_owned(
+        slots: Slots,
+        stats: Stats,
+        inventory: Vec<u8>,
+        purchases: Vec<u8>,
+        budget: f64,
+    ) -> Self {
         Self {
             slots,
             stats: StatsHandle::Owned(stats),
+            inventory: Cow::Owned(inventory),
+            purchases: Cow::Owned(purchases),
+            budget,
         }
     }
 
-    fn new_borrowed(slots: u8, stats: &'sh Stats) -> Self {
+    fn new_borrowed(
+        slots: Slots,
+        stats: &'sh Stats,
+        inventory: &'sh [u8],
+        purchases: &'sh [u8],
+        budget: f64,
+    ) -> Self {
         Self {
             slots,
             stats: StatsHandle::Borrowed(stats),
+            inventory: Cow::Borrowed(inventory),
+            purchases: Cow::Borrowed(purchases),
+            budget,
         }
     }
 }
 
 impl<'sh> PartialEq for CacheKey<'sh> {
     fn eq(&self, other: &Self) -> bool {
-        self.slots == other.slots && self.stats == other.stats
+        self.slots == other.slots
+            && self.stats == other.stats
+            && self.inventory == other.inventory
+            && self.purchases == other.purchases
+            && self.budget.to_bits() == other.budget.to_bits()
     }
 }
 
@@ -268,5 +1582,8 @@ impl<'sh> Hash for CacheKey<'sh> {
     fn hash<H: Hasher>(&self, state: &mut H) {
         self.slots.hash(state);
         self.stats.hash(state);
+        self.inventory.hash(state);
+        self.purchases.hash(state);
+        self.budget.to_bits().hash(state);
     }
 }
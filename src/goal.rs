@@ -0,0 +1,620 @@
+use crate::stats::Stats;
+use std::{error::Error, fmt};
+
+/// A goal for the stats of an item, i.e. a predicate that the stats of an item
+/// either meet or don't meet.
+///
+/// The simplest kind of goal is `Goal::AtLeast`, which is met iff _every_ stat
+/// meets or exceeds a threshold. More complicated goals are built up out of
+/// comparisons of weighted sums of stats (`Goal::Linear`), and boolean
+/// combinations of other goals. Goals can also be parsed from a small
+/// expression language; see `Goal::parse`.
+///
+/// ## Invariants:
+///
+/// - Every threshold and every list of weights has the same length as the
+///   stat arrays of the items and scrolls that the goal is used with.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Goal {
+    /// Met iff every stat meets or exceeds the corresponding stat of the
+    /// threshold.
+    AtLeast(Stats),
+    /// Met iff the sum of each stat multiplied by its corresponding weight
+    /// compares to `rhs` as `cmp` says it should, e.g. "WATK + STR/2 ≥ 110".
+    Linear {
+        weights: Vec<f64>,
+        cmp: Cmp,
+        rhs: f64,
+    },
+    /// Met iff every one of the goals is met. This is always met if there are
+    /// no goals.
+    All(Vec<Goal>),
+    /// Met iff any one of the goals is met. This is never met if there are no
+    /// goals.
+    Any(Vec<Goal>),
+    /// Met iff the goal is _not_ met.
+    Not(Box<Goal>),
+}
+
+/// A comparison operator, as used by `Goal::Linear`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Cmp {
+    Lt,
+    Le,
+    Eq,
+    Ne,
+    Ge,
+    Gt,
+}
+
+impl Goal {
+    /// Creates a new goal that is met iff any one of `alternatives` is met,
+    /// in the sense of `Goal::AtLeast`. For example, "≥108 WATK, or ≥106 WATK
+    /// with ≥10 STR" is a goal with two alternatives.
+    pub fn from_vec(alternatives: Vec<Stats>) -> Self {
+        Self::Any(alternatives.into_iter().map(Self::AtLeast).collect())
+    }
+
+    /// Parses a goal from `src`, where `names` are the names of the stats, in
+    /// order of index. The syntax is that of comparisons between arithmetic
+    /// expressions, combined with boolean operators:
+    ///
+    /// - Arithmetic expressions are made of numbers, stat names, `+`, `-`,
+    ///   `*`, `/`, and parentheses. They must be linear, i.e. stats can be
+    ///   multiplied or divided by numbers, but not by other stats.
+    /// - The comparison operators are `<`, `<=`, `=` (or `==`), `!=`, `>=`,
+    ///   and `>`. Comparisons can be chained, as in `100 <= WATK <= 110`.
+    /// - The boolean operators are `!` (or `not`), `&&` (or `and`), and `||`
+    ///   (or `or`), from highest to lowest precedence.
+    ///
+    /// So, for example, `WATK >= 108 || (WATK >= 106 && STR >= 10)` and
+    /// `WATK + STR / 2 >= 110` are both goals, given the right `names`.
+    pub fn parse(src: &str, names: &[&str]) -> Result<Self, ParseGoalError> {
+        let mut parser = Parser {
+            tokens: tokenize(src)?,
+            pos: 0,
+            end: src.len(),
+            names,
+        };
+
+        let goal = parser.or()?;
+        if parser.pos < parser.tokens.len() {
+            return Err(parser.error("expected end of input"));
+        }
+
+        Ok(goal)
+    }
+
+    /// Returns `true` iff an item with stats `stats` meets this goal.
+    pub fn is_met(&self, stats: &Stats) -> bool {
+        match self {
+            Self::AtLeast(threshold) => stats >= threshold,
+            Self::Linear { weights, cmp, rhs } => {
+                debug_assert_eq!(weights.len(), stats.len());
+
+                let lhs = weights
+                    .iter()
+                    .zip(stats.iter())
+                    .map(|(weight, stat)| weight * f64::from(*stat))
+                    .sum();
+
+                cmp.holds(lhs, *rhs)
+            }
+            Self::All(goals) => goals.iter().all(|goal| goal.is_met(stats)),
+            Self::Any(goals) => goals.iter().any(|goal| goal.is_met(stats)),
+            Self::Not(goal) => !goal.is_met(stats),
+        }
+    }
+
+    /// Returns `false` only if no item whose stats lie between `lo` and `hi`
+    /// (inclusive, in every stat) meets this goal. This is how the "master
+    /// scroll" heuristic decides whether or not a goal can still be reached.
+    ///
+    /// This is conservative, so it may return `true` even if no such item
+    /// meets this goal, but it is always exact for goals built solely out of
+    /// `Goal::AtLeast` and `Goal::Any`.
+    pub fn may_be_met(&self, lo: &Stats, hi: &Stats) -> bool {
+        self.bounds(lo, hi).0
+    }
+
+    /// For items whose stats lie between `lo` and `hi` (inclusive, in every
+    /// stat), returns whether this goal is possibly met by any of them, and
+    /// whether it's necessarily met by all of them. The former may be `true`
+    /// when it shouldn't be, and the latter may be `false` when it shouldn't
+    /// be, but never the other way around.
+    fn bounds(&self, lo: &Stats, hi: &Stats) -> (bool, bool) {
+        match self {
+            Self::AtLeast(threshold) => (hi >= threshold, lo >= threshold),
+            Self::Linear { weights, cmp, rhs } => {
+                debug_assert_eq!(weights.len(), lo.len());
+                debug_assert_eq!(weights.len(), hi.len());
+
+                let (mut min, mut max) = (0.0, 0.0);
+                for ((weight, lo_stat), hi_stat) in
+                    weights.iter().zip(lo.iter()).zip(hi.iter())
+                {
+                    let (lo_term, hi_term) = (
+                        weight * f64::from(*lo_stat),
+                        weight * f64::from(*hi_stat),
+                    );
+
+                    min += lo_term.min(hi_term);
+                    max += lo_term.max(hi_term);
+                }
+
+                cmp.bounds(min, max, *rhs)
+            }
+            Self::All(goals) => goals.iter().fold(
+                (true, true),
+                |(possibly, necessarily), goal| {
+                    let (goal_possibly, goal_necessarily) =
+                        goal.bounds(lo, hi);
+
+                    (
+                        possibly && goal_possibly,
+                        necessarily && goal_necessarily,
+                    )
+                },
+            ),
+            Self::Any(goals) => goals.iter().fold(
+                (false, false),
+                |(possibly, necessarily), goal| {
+                    let (goal_possibly, goal_necessarily) =
+                        goal.bounds(lo, hi);
+
+                    (
+                        possibly || goal_possibly,
+                        necessarily || goal_necessarily,
+                    )
+                },
+            ),
+            Self::Not(goal) => {
+                let (possibly, necessarily) = goal.bounds(lo, hi);
+
+                (!necessarily, !possibly)
+            }
+        }
+    }
+}
+
+impl From<Stats> for Goal {
+    /// Creates a goal that is met iff every stat meets or exceeds the
+    /// corresponding stat of `stats`.
+    fn from(stats: Stats) -> Self {
+        Self::AtLeast(stats)
+    }
+}
+
+impl Cmp {
+    /// Returns `true` iff `lhs` compares to `rhs` as this operator says it
+    /// should.
+    fn holds(self, lhs: f64, rhs: f64) -> bool {
+        match self {
+            Self::Lt => lhs < rhs,
+            Self::Le => lhs <= rhs,
+            Self::Eq => lhs == rhs,
+            Self::Ne => lhs != rhs,
+            Self::Ge => lhs >= rhs,
+            Self::Gt => lhs > rhs,
+        }
+    }
+
+    /// Given that the left-hand side lies between `min` and `max`
+    /// (inclusive), returns whether this comparison possibly holds, and
+    /// whether it necessarily holds.
+    fn bounds(self, min: f64, max: f64, rhs: f64) -> (bool, bool) {
+        let (contains, exactly) =
+            (min <= rhs && rhs <= max, min == rhs && max == rhs);
+
+        match self {
+            Self::Lt => (min < rhs, max < rhs),
+            Self::Le => (min <= rhs, max <= rhs),
+            Self::Eq => (contains, exactly),
+            Self::Ne => (!exactly, !contains),
+            Self::Ge => (max >= rhs, min >= rhs),
+            Self::Gt => (max > rhs, min > rhs),
+        }
+    }
+}
+
+/// An error encountered by `Goal::parse`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseGoalError {
+    position: usize,
+    message: String,
+}
+
+impl ParseGoalError {
+    /// The byte offset into the source at which the error was encountered.
+    pub const fn position(&self) -> usize {
+        self.position
+    }
+
+    /// A description of the error.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for ParseGoalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (at byte {})", self.message, self.position)
+    }
+}
+
+impl Error for ParseGoalError {}
+
+/// A token of the goal expression language, as produced by `tokenize`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Token<'s> {
+    Num(f64),
+    Name(&'s str),
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    LParen,
+    RParen,
+    Not,
+    And,
+    Or,
+    Cmp(Cmp),
+}
+
+/// Splits `src` into tokens, each paired with its byte offset into `src`.
+fn tokenize(src: &str) -> Result<Vec<(usize, Token<'_>)>, ParseGoalError> {
+    let bytes = src.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let start = i;
+        let next = bytes.get(i + 1).copied();
+
+        let token = match bytes[i] {
+            b if b.is_ascii_whitespace() => {
+                i += 1;
+
+                continue;
+            }
+            b if b.is_ascii_digit() || b == b'.' => {
+                while i < bytes.len()
+                    && (bytes[i].is_ascii_digit() || bytes[i] == b'.')
+                {
+                    i += 1;
+                }
+
+                Token::Num(src[start..i].parse().map_err(|_| {
+                    ParseGoalError {
+                        position: start,
+                        message: format!(
+                            "invalid number `{}`",
+                            &src[start..i]
+                        ),
+                    }
+                })?)
+            }
+            b if b.is_ascii_alphabetic() || b == b'_' => {
+                while i < bytes.len()
+                    && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_')
+                {
+                    i += 1;
+                }
+
+                match &src[start..i] {
+                    "not" => Token::Not,
+                    "and" => Token::And,
+                    "or" => Token::Or,
+                    name => Token::Name(name),
+                }
+            }
+            b'&' if next == Some(b'&') => {
+                i += 2;
+
+                Token::And
+            }
+            b'|' if next == Some(b'|') => {
+                i += 2;
+
+                Token::Or
+            }
+            b'<' | b'>' | b'=' | b'!' if next == Some(b'=') => {
+                i += 2;
+
+                Token::Cmp(match bytes[start] {
+                    b'<' => Cmp::Le,
+                    b'>' => Cmp::Ge,
+                    b'=' => Cmp::Eq,
+                    _ => Cmp::Ne,
+                })
+            }
+            b => {
+                i += 1;
+
+                match b {
+                    b'+' => Token::Plus,
+                    b'-' => Token::Minus,
+                    b'*' => Token::Star,
+                    b'/' => Token::Slash,
+                    b'(' => Token::LParen,
+                    b')' => Token::RParen,
+                    b'!' => Token::Not,
+                    b'<' => Token::Cmp(Cmp::Lt),
+                    b'>' => Token::Cmp(Cmp::Gt),
+                    b'=' => Token::Cmp(Cmp::Eq),
+                    _ => {
+                        let c =
+                            src[start..].chars().next().unwrap_or_default();
+
+                        return Err(ParseGoalError {
+                            position: start,
+                            message: format!("unexpected character `{}`", c),
+                        });
+                    }
+                }
+            }
+        };
+
+        tokens.push((start, token));
+    }
+
+    Ok(tokens)
+}
+
+/// A linear combination of stats, plus a constant, as produced while parsing
+/// arithmetic expressions.
+#[derive(Clone, Debug)]
+struct Linear {
+    weights: Vec<f64>,
+    constant: f64,
+}
+
+impl Linear {
+    /// Returns `true` iff this doesn't depend on any stat.
+    fn is_constant(&self) -> bool {
+        self.weights.iter().all(|weight| *weight == 0.0)
+    }
+
+    /// Multiplies every weight, and the constant, by `factor`.
+    fn scale(mut self, factor: f64) -> Self {
+        for weight in self.weights.iter_mut() {
+            *weight *= factor;
+        }
+        self.constant *= factor;
+
+        self
+    }
+
+    /// Adds `other`, multiplied by `sign`, to this.
+    fn add(mut self, other: &Self, sign: f64) -> Self {
+        for (weight, other_weight) in
+            self.weights.iter_mut().zip(other.weights.iter())
+        {
+            *weight += sign * other_weight;
+        }
+        self.constant += sign * other.constant;
+
+        self
+    }
+}
+
+/// A recursive descent parser for the goal expression language. See
+/// `Goal::parse`.
+struct Parser<'s, 'n> {
+    tokens: Vec<(usize, Token<'s>)>,
+    pos: usize,
+    /// The length of the source, which is used as the position of errors
+    /// encountered at the end of the input.
+    end: usize,
+    names: &'n [&'n str],
+}
+
+impl<'s, 'n> Parser<'s, 'n> {
+    fn peek(&self) -> Option<Token<'s>> {
+        self.tokens.get(self.pos).map(|(_, token)| *token)
+    }
+
+    /// Consumes the next token iff it's `token`, and returns whether or not
+    /// it was consumed.
+    fn eat(&mut self, token: Token<'_>) -> bool {
+        if self.peek() == Some(token) {
+            self.pos += 1;
+
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Creates an error at the position of the next token.
+    fn error(&self, message: &str) -> ParseGoalError {
+        ParseGoalError {
+            position: self
+                .tokens
+                .get(self.pos)
+                .map_or(self.end, |(position, _)| *position),
+            message: message.to_owned(),
+        }
+    }
+
+    /// `or := and (("||" | "or") and)*`
+    fn or(&mut self) -> Result<Goal, ParseGoalError> {
+        let mut goals = vec![self.and()?];
+        while self.eat(Token::Or) {
+            goals.push(self.and()?);
+        }
+
+        Ok(if goals.len() == 1 {
+            goals.pop().unwrap_or_else(|| unreachable!())
+        } else {
+            Goal::Any(goals)
+        })
+    }
+
+    /// `and := unary (("&&" | "and") unary)*`
+    fn and(&mut self) -> Result<Goal, ParseGoalError> {
+        let mut goals = vec![self.unary()?];
+        while self.eat(Token::And) {
+            goals.push(self.unary()?);
+        }
+
+        Ok(if goals.len() == 1 {
+            goals.pop().unwrap_or_else(|| unreachable!())
+        } else {
+            Goal::All(goals)
+        })
+    }
+
+    /// `unary := ("!" | "not") unary | "(" or ")" | comparison`
+    fn unary(&mut self) -> Result<Goal, ParseGoalError> {
+        if self.eat(Token::Not) {
+            return Ok(Goal::Not(Box::new(self.unary()?)));
+        }
+
+        // A parenthesis could open either a goal, or an arithmetic expression
+        // that is the left-hand side of a comparison, so we try the former
+        // first, and backtrack if that doesn't work out.
+        let start = self.pos;
+        if self.eat(Token::LParen) {
+            if let Ok(goal) = self.or() {
+                if self.eat(Token::RParen) {
+                    return Ok(goal);
+                }
+            }
+
+            self.pos = start;
+        }
+
+        self.comparison()
+    }
+
+    /// `comparison := sum (cmp sum)+`
+    fn comparison(&mut self) -> Result<Goal, ParseGoalError> {
+        let mut lhs = self.sum()?;
+        let mut goals = Vec::new();
+
+        while let Some(Token::Cmp(cmp)) = self.peek() {
+            self.pos += 1;
+            let rhs = self.sum()?;
+
+            // `lhs cmp rhs` is the same as `lhs - rhs cmp 0`.
+            let diff = lhs.clone().add(&rhs, -1.0);
+            goals.push(Goal::Linear {
+                weights: diff.weights,
+                cmp,
+                rhs: -diff.constant,
+            });
+
+            lhs = rhs;
+        }
+
+        match goals.len() {
+            0 => Err(self.error("expected a comparison operator")),
+            1 => Ok(goals.pop().unwrap_or_else(|| unreachable!())),
+            _ => Ok(Goal::All(goals)),
+        }
+    }
+
+    /// `sum := term (("+" | "-") term)*`
+    fn sum(&mut self) -> Result<Linear, ParseGoalError> {
+        let mut sum = self.term()?;
+
+        loop {
+            let sign = if self.eat(Token::Plus) {
+                1.0
+            } else if self.eat(Token::Minus) {
+                -1.0
+            } else {
+                return Ok(sum);
+            };
+
+            sum = sum.add(&self.term()?, sign);
+        }
+    }
+
+    /// `term := factor (("*" | "/") factor)*`
+    fn term(&mut self) -> Result<Linear, ParseGoalError> {
+        let mut term = self.factor()?;
+
+        loop {
+            if self.eat(Token::Star) {
+                let position = self.pos;
+                let factor = self.factor()?;
+
+                term = if factor.is_constant() {
+                    term.scale(factor.constant)
+                } else if term.is_constant() {
+                    factor.scale(term.constant)
+                } else {
+                    self.pos = position;
+
+                    return Err(self.error("stats cannot be multiplied"));
+                };
+            } else if self.eat(Token::Slash) {
+                let position = self.pos;
+                let factor = self.factor()?;
+
+                if !factor.is_constant() || factor.constant == 0.0 {
+                    self.pos = position;
+
+                    return Err(
+                        self.error("can only divide by a nonzero number")
+                    );
+                }
+
+                term = term.scale(1.0 / factor.constant);
+            } else {
+                return Ok(term);
+            }
+        }
+    }
+
+    /// `factor := number | name | "-" factor | "(" sum ")"`
+    fn factor(&mut self) -> Result<Linear, ParseGoalError> {
+        let zero = Linear {
+            weights: vec![0.0; self.names.len()],
+            constant: 0.0,
+        };
+
+        match self.peek() {
+            Some(Token::Num(n)) => {
+                self.pos += 1;
+
+                Ok(Linear {
+                    constant: n,
+                    ..zero
+                })
+            }
+            Some(Token::Name(name)) => {
+                let i = self
+                    .names
+                    .iter()
+                    .position(|stat_name| *stat_name == name)
+                    .ok_or_else(|| {
+                        self.error(&format!("unknown stat `{}`", name))
+                    })?;
+                self.pos += 1;
+
+                let mut linear = zero;
+                linear.weights[i] = 1.0;
+
+                Ok(linear)
+            }
+            Some(Token::Minus) => {
+                self.pos += 1;
+
+                Ok(self.factor()?.scale(-1.0))
+            }
+            Some(Token::LParen) => {
+                self.pos += 1;
+                let sum = self.sum()?;
+                if !self.eat(Token::RParen) {
+                    return Err(self.error("expected `)`"));
+                }
+
+                Ok(sum)
+            }
+            _ => Err(self.error("expected a number, a stat, or `(`")),
+        }
+    }
+}
@@ -1,18 +1,47 @@
-use crate::{scroll::Scroll, stats::Stats};
+use crate::{
+    scroll::{self, Scroll},
+    stats::Stats,
+};
 use std::rc::Rc;
 
 /// The state of an item, including how many slots it has left, and what its
-/// stats are. This is a node of a scrolling strategy tree, so it also can have
+/// stats are. The state also includes:
+///
+/// - How much of the budget for scrolling this item is left, as no scroll
+///   that costs more than that can be used on the item. This is positive
+///   infinity when there is no budget.
+/// - How many of its slots were lost to failed scrolls, as those slots can be
+///   recovered by a Clean Slate Scroll (see `ScrollEffect::CleanSlate`), and
+///   can change the probability of success (see `ItemContext`).
+/// - How many more times a hammer can be used to add a slot to the item (see
+///   `ScrollEffect::Hammer`).
+/// - How many scrolls in a row have missed, and how many upgrades the item
+///   has had, as those can change the probability of success of the next
+///   scroll (see `Scroll::success_rate`).
+/// - How many are left of each scroll that there are only so many of (see
+///   `Scroll::inventory`).
+/// - How many have been bought of each scroll whose price depends on that
+///   (see `Scroll::pricing`).
+///
+/// This is a node of a scrolling strategy tree, so it also can have
 /// (or may not have) a single "child" of type `ScrollUse`. This only supports
 /// one child at most, because we only want to keep the _optimal_ scroll usage
 /// in memory; others should be, and are, discarded.
 ///
 /// This is an enum because we want a way to represent the "state" of an item
 /// that no longer exists, because it was boomed by a dark scroll.
+#[derive(Clone)]
 pub enum ItemState<'a> {
     Exists {
         slots: u8,
         stats: Stats,
+        budget: f64,
+        failed_slots: u8,
+        hammers: u8,
+        streak: u8,
+        upgrades: u8,
+        inventory: Vec<u8>,
+        purchases: Vec<u8>,
         child: Option<Rc<ScrollUse<'a>>>,
     },
     Boomed,
@@ -20,15 +49,109 @@ pub enum ItemState<'a> {
 
 impl<'a> ItemState<'a> {
     /// Creates a new instance of this type, specifically of the
-    /// `ItemState::Exists` variant. The child is defaulted to `None`.
+    /// `ItemState::Exists` variant. There is no budget (i.e. the budget is
+    /// positive infinity), no hammers can be used, and the child is defaulted
+    /// to `None`.
     pub const fn new_exists(slots: u8, stats: Stats) -> Self {
+        Self::new_exists_with_budget(slots, stats, f64::INFINITY)
+    }
+
+    /// Creates a new instance of this type, specifically of the
+    /// `ItemState::Exists` variant, with `budget` left to spend on scrolls.
+    /// No slots have been lost to failed scrolls, no hammers can be used, no
+    /// scrolls have missed in a row, the item hasn't been upgraded, there's no
+    /// limit on how many of any scroll can be used, none have been bought
+    /// yet, and the child is defaulted to `None`.
+    pub const fn new_exists_with_budget(
+        slots: u8,
+        stats: Stats,
+        budget: f64,
+    ) -> Self {
         Self::Exists {
             slots,
             stats,
+            budget,
+            failed_slots: 0,
+            hammers: 0,
+            streak: 0,
+            upgrades: 0,
+            inventory: Vec::new(),
+            purchases: Vec::new(),
             child: None,
         }
     }
 
+    /// Returns this item state, except that `failed_slots` of the item's
+    /// slots have already been lost to failed scrolls (see
+    /// `ScrollEffect::CleanSlate`). This does nothing to an item that has been
+    /// boomed.
+    pub fn with_failed_slots(mut self, failed_slots: u8) -> Self {
+        if let Self::Exists {
+            failed_slots: f, ..
+        } = &mut self
+        {
+            *f = failed_slots;
+        }
+
+        self
+    }
+
+    /// Returns this item state, except that a hammer can be used on the item
+    /// `hammers` more times (see `ScrollEffect::Hammer`). This does nothing to
+    /// an item that has been boomed.
+    pub fn with_hammers(mut self, hammers: u8) -> Self {
+        if let Self::Exists { hammers: h, .. } = &mut self {
+            *h = hammers;
+        }
+
+        self
+    }
+
+    /// Returns this item state, except that the last `streak` scrolls used
+    /// on the item have all missed (see `Scroll::success_rate`). This does
+    /// nothing to an item that has been boomed.
+    pub fn with_streak(mut self, streak: u8) -> Self {
+        if let Self::Exists { streak: s, .. } = &mut self {
+            *s = streak;
+        }
+
+        self
+    }
+
+    /// Returns this item state, except that the item has already had
+    /// `upgrades` upgrades (see `ItemContext::upgrades`). This does nothing
+    /// to an item that has been boomed.
+    pub fn with_upgrades(mut self, upgrades: u8) -> Self {
+        if let Self::Exists { upgrades: u, .. } = &mut self {
+            *u = upgrades;
+        }
+
+        self
+    }
+
+    /// Returns this item state, except that `inventory[i]` of the scrolls
+    /// whose `Scroll::inventory` is `Some(i)` are left to use on the item.
+    /// This does nothing to an item that has been boomed.
+    pub fn with_inventory(mut self, inventory: Vec<u8>) -> Self {
+        if let Self::Exists { inventory: inv, .. } = &mut self {
+            *inv = inventory;
+        }
+
+        self
+    }
+
+    /// Returns this item state, except that `purchases[i]` of the scrolls
+    /// whose `Pricing::purchases` is `i` have already been bought (see
+    /// `Scroll::pricing`). Missing entries count as none bought. This does
+    /// nothing to an item that has been boomed.
+    pub fn with_purchases(mut self, purchases: Vec<u8>) -> Self {
+        if let Self::Exists { purchases: p, .. } = &mut self {
+            *p = purchases;
+        }
+
+        self
+    }
+
     /// Creates a new instance of this type, specifically of the
     /// `ItemState::Boomed` variant. This contains no useful information other
     /// than that the item was boomed.
@@ -37,54 +160,136 @@ impl<'a> ItemState<'a> {
     }
 }
 
-/// An instance of a particular scroll being used on a particular `ItemState`.
-/// Like `ItemState`, this type represents a part of a scrolling strategy tree;
-/// in particular, this represents a different kind of node than an `ItemState`
+/// An action that can be taken on an item that exists.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action<'a> {
+    /// Use the given scroll on the item.
+    Scroll(&'a Scroll),
+    /// Stop scrolling, and keep the item as-is. This is the only action
+    /// available to an item with no slots remaining, but it can also be taken
+    /// early, e.g. when the goal has already been reached, or has become
+    /// unreachable.
+    Stop,
+}
+
+/// An instance of a particular scroll being used on a particular `ItemState`
+/// (or, more generally, of a particular `Action` being taken on it). Like
+/// `ItemState`, this type represents a part of a scrolling strategy tree; in
+/// particular, this represents a different kind of node than an `ItemState`
 /// does.
 ///
 /// The child nodes here are themselves `ItemStates`, representing all possible
-/// outcomes of this scroll usage. The outcomes are stored in their own
-/// special-sauce type, `Outcomes`.
+/// outcomes of this scroll usage, each with the probability of that outcome
+/// occurring. The outcomes are stored in their own special-sauce type,
+/// `Outcomes`. When the action is `Action::Stop`, there are no outcomes, as
+/// the item is simply kept as-is.
 ///
 /// This struct contains a member representing the probability of reaching the
 /// goal given that this scroll is chosen (but not assuming any particular
 /// outcome of the scroll). There is also a member of this struct representing
 /// the expected cost (due solely to scroll expenditure) incurred due to this
 /// scroll being used, in addition to all future scrolls used. The future
-/// scroll costs are calculated optimally, as usual.
+/// scroll costs are calculated optimally, as usual. Finally, there is a member
+/// representing the expected reward that the item earns once scrolling stops.
+///
+/// Exactly what "optimally" means, and how items are valued once scrolling
+/// stops, is up to the `Objective` that the strategy tree was searched for.
+///
+/// If one of the outcomes of the scroll would leave the item exactly as it
+/// was (e.g. a failure of a scroll used alongside a White Scroll), then the
+/// scroll is simply used again until something else happens. In that case,
+/// that outcome isn't one of the children, the probabilities of the children
+/// are conditioned on it not happening, and the expected cost includes every
+/// use of the scroll.
+///
+/// When a scroll can recover failed slots (see `ScrollEffect::CleanSlate`), or
+/// reset the item (see `ScrollEffect::Reset`), an item can end up back in a
+/// state that it was in before, so the optimal strategy may go around in
+/// circles. Strategy trees can't, though, so the circles are instead unrolled
+/// some number of times. See `dfs::solve`.
 pub struct ScrollUse<'a> {
     /// "Probability of goal": Represents the probability of reaching the goal
     /// given that this scroll is chosen (but not assuming any particular
     /// outcome of the scroll).
     pub p_goal: f64,
+    /// "Probabilities of goals": For objectives that have more than one goal
+    /// (see `Lexicographic`), represents the probability of reaching each of
+    /// them, in the same way that `p_goal` does for a single goal. This is
+    /// empty for objectives that have just the one goal.
+    pub p_goals: Vec<f64>,
     /// "Expected cost": Represents the expected cost (due solely to scroll
     /// expenditure) incurred due to this scroll being used, in addition to all
     /// future scrolls used. The future scroll costs are calculated optimally,
     /// as usual.
     pub exp_cost: f64,
-    /// The scroll being used.
-    scroll: &'a Scroll,
+    /// "Expected costs": Like `exp_cost`, but separately for each currency
+    /// (see `Scroll::costs`), rather than in total. This has an entry for
+    /// every currency that any of the scrolls used costs, and is empty only
+    /// if no scroll is used at all.
+    pub exp_costs: Vec<f64>,
+    /// "Expected reward": Represents the expected reward (as defined by the
+    /// `Objective`) that the item earns once scrolling stops, given that this
+    /// scroll is chosen. This is always zero for objectives that don't value
+    /// items beyond whether or not they reach the goal.
+    pub exp_reward: f64,
+    /// The action being taken, usually a scroll being used.
+    action: Action<'a>,
     /// All possible outcomes of this scroll usage; the children of this node.
     outcomes: Outcomes<'a>,
 }
 
 impl<'a> ScrollUse<'a> {
     /// Creates a new scroll usage struct, given a particular scroll that is
-    /// being used. The probability of reaching the goal defaults to zero, the
-    /// expected cost defaults to the cost of `scroll`, and there are no
+    /// being used. The probability of reaching the goal defaults to zero (and
+    /// there are no probabilities of reaching multiple goals), the expected
+    /// costs per currency default to the costs of `scroll` (see
+    /// `Scroll::costs`), the expected cost defaults to their total at `rates`
+    /// apiece (see `Objective::rates`), the expected reward defaults to zero,
+    /// and there are no outcomes/children.
+    pub fn new(scroll: &'a Scroll, rates: &[f64]) -> Self {
+        let costs = scroll.costs();
+
+        Self {
+            p_goal: 0.0,
+            p_goals: Vec::new(),
+            exp_cost: scroll::exchange(&costs, rates),
+            exp_costs: costs,
+            exp_reward: 0.0,
+            action: Action::Scroll(scroll),
+            outcomes: Outcomes::new(),
+        }
+    }
+
+    /// Creates a new "scroll usage" struct that actually represents no scroll
+    /// being used at all, i.e. `Action::Stop`. The probability of reaching the
+    /// goal, the expected cost, and the expected reward all default to zero
+    /// (see `Objective::leaf`), there are no probabilities of reaching
+    /// multiple goals or expected costs per currency, and there are no
     /// outcomes/children.
-    pub const fn new(scroll: &'a Scroll) -> Self {
+    pub const fn new_stop() -> Self {
         Self {
             p_goal: 0.0,
-            exp_cost: scroll.cost,
-            scroll,
+            p_goals: Vec::new(),
+            exp_cost: 0.0,
+            exp_costs: Vec::new(),
+            exp_reward: 0.0,
+            action: Action::Stop,
             outcomes: Outcomes::new(),
         }
     }
 
-    /// Returns a reference to the scroll being used here.
-    pub const fn scroll(&self) -> &'a Scroll {
-        self.scroll
+    /// Returns the action being taken here.
+    pub const fn action(&self) -> Action<'a> {
+        self.action
+    }
+
+    /// Returns a reference to the scroll being used here, or `None` if the
+    /// action being taken here is `Action::Stop`.
+    pub const fn scroll(&self) -> Option<&'a Scroll> {
+        match self.action {
+            Action::Scroll(scroll) => Some(scroll),
+            Action::Stop => None,
+        }
     }
 
     /// Returns a reference to the internal representation of the set of
@@ -93,22 +298,59 @@ impl<'a> ScrollUse<'a> {
         self.outcomes.outcomes()
     }
 
-    /// Adds a new outcome to this `ScrollUse`'s set of outcomes, and returns a
-    /// mutable reference to the newly added outcome.
+    /// Returns the probability of each possible outcome occurring, in the
+    /// same order as `ScrollUse::outcomes`. Note that outcomes from which
+    /// nothing could be gained may have been left out entirely, so these
+    /// probabilities need not sum to one.
+    pub fn p_outcomes(&self) -> &[f64] {
+        self.outcomes.p_outcomes()
+    }
+
+    /// Adds a new outcome, which occurs with probability `p_outcome`, to this
+    /// `ScrollUse`'s set of outcomes, and returns a mutable reference to the
+    /// newly added outcome.
     pub fn push_outcome(
         &mut self,
+        p_outcome: f64,
         outcome: ItemState<'a>,
     ) -> &mut ItemState<'a> {
-        self.outcomes.push_outcome(outcome)
+        self.outcomes.push_outcome(p_outcome, outcome)
     }
 }
 
+/// The strategies for an item whose initial state is random, along with what
+/// they're worth on average. See `dfs::solve_random`.
+///
+/// Each of the values here is the expectation, over the initial states, of
+/// the same member of the root `ScrollUse` of each strategy tree, counting a
+/// boomed initial state as zero for all of them. Unlike a `ScrollUse`, this
+/// isn't a node of any strategy tree.
+pub struct RandomStrategy<'a> {
+    /// The probability of reaching the goal from a random initial state.
+    pub p_goal: f64,
+    /// The probability of reaching each goal from a random initial state.
+    /// See `ScrollUse::p_goals`.
+    pub p_goals: Vec<f64>,
+    /// The expected cost of scrolling an item from a random initial state.
+    pub exp_cost: f64,
+    /// Like `exp_cost`, but separately for each currency. See
+    /// `ScrollUse::exp_costs`.
+    pub exp_costs: Vec<f64>,
+    /// The expected reward of an item from a random initial state.
+    pub exp_reward: f64,
+    /// The root of the strategy tree for each initial state, in the same
+    /// order as the initial states, or `None` for an initial state that is
+    /// boomed.
+    pub strategies: Vec<Option<Rc<ScrollUse<'a>>>>,
+}
+
 /// All possible outcomes of a particular scroll usage. Each outcome is
 /// represented as an `ItemState`. See the documentation for `ScrollUse` (and
 /// for `ItemState`) for more info.
 #[derive(Default)]
 struct Outcomes<'a> {
     outcomes: Vec<ItemState<'a>>,
+    p_outcomes: Vec<f64>,
 }
 
 impl<'a> Outcomes<'a> {
@@ -116,6 +358,7 @@ impl<'a> Outcomes<'a> {
     pub const fn new() -> Self {
         Self {
             outcomes: Vec::new(),
+            p_outcomes: Vec::new(),
         }
     }
 
@@ -125,13 +368,22 @@ impl<'a> Outcomes<'a> {
         &self.outcomes
     }
 
-    /// Adds a new outcome to this set of outcomes, and returns a mutable
-    /// reference to the newly added outcome.
+    /// Returns a reference to the internal representation of the
+    /// probabilities of the outcomes.
+    pub fn p_outcomes(&self) -> &[f64] {
+        &self.p_outcomes
+    }
+
+    /// Adds a new outcome, which occurs with probability `p_outcome`, to this
+    /// set of outcomes, and returns a mutable reference to the newly added
+    /// outcome.
     pub fn push_outcome(
         &mut self,
+        p_outcome: f64,
         outcome: ItemState<'a>,
     ) -> &mut ItemState<'a> {
         self.outcomes.push(outcome);
+        self.p_outcomes.push(p_outcome);
 
         self.outcomes.last_mut().unwrap_or_else(|| unreachable!())
     }
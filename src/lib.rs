@@ -3,14 +3,61 @@
 #![deny(deprecated)]
 
 pub mod dfs;
+pub mod goal;
 pub mod graph;
+pub mod objective;
+pub mod reward;
 pub mod scroll;
 pub mod stats;
 
+/// The usual scrolls for a Toy of 101: 10% and 30% (dark) scrolls for +5
+/// WATK, +3 STR, and +1 LUK, 60% and 70% (dark) scrolls for +2 WATK and +1
+/// STR, and 100% scrolls for +1 WATK. Stats are WATK, STR, DEX, and LUK, in
+/// that order.
+#[cfg(test)]
+fn toy_of_101_scrolls() -> [scroll::Scroll; 5] {
+    use crate::{scroll::Scroll, stats::Stats};
+
+    [
+        Scroll::new(0.1, false, 100_000.0, Stats::from_vec(vec![5, 3, 0, 1])),
+        Scroll::new(0.3, true, 1_300_000.0, Stats::from_vec(vec![5, 3, 0, 1])),
+        Scroll::new(0.6, false, 40_000.0, Stats::from_vec(vec![2, 1, 0, 0])),
+        Scroll::new(0.7, true, 45_000.0, Stats::from_vec(vec![2, 1, 0, 0])),
+        Scroll::new(1.0, false, 70_000.0, Stats::from_vec(vec![1, 0, 0, 0])),
+    ]
+}
+
+/// Solves for the strategy that maximises the probability of reaching `watk`
+/// WATK from `init_state`, whose only stat is WATK, and returns the root
+/// `ScrollUse` of its strategy tree.
+#[cfg(test)]
+fn solve_watk<'a>(
+    scrolls: &'a [scroll::Scroll],
+    init_state: graph::ItemState<'a>,
+    watk: u16,
+) -> std::rc::Rc<graph::ScrollUse<'a>> {
+    use crate::{dfs::solve_p, goal::Goal, graph::ItemState, stats::Stats};
+
+    let mut init_state = init_state;
+    solve_p(
+        &mut init_state,
+        scrolls,
+        &Goal::from(Stats::from_vec(vec![watk])),
+        &[],
+    );
+
+    if let ItemState::Exists { child, .. } = init_state {
+        child.unwrap()
+    } else {
+        unreachable!()
+    }
+}
+
 #[test]
 fn toy_of_101_test() {
     use crate::{
-        dfs::solve_p, graph::ItemState, scroll::Scroll, stats::Stats,
+        dfs::solve_p, goal::Goal, graph::ItemState, scroll::Scroll,
+        stats::Stats,
     };
 
     let mut init_state =
@@ -26,27 +73,1672 @@ fn toy_of_101_test() {
     solve_p(
         &mut init_state,
         &scrolls,
-        &Stats::from_vec(vec![111, 0, 0, 0]),
+        &Goal::from(Stats::from_vec(vec![111, 0, 0, 0])),
+        &[],
     );
 
-    if let ItemState::Exists {
-        slots: _,
-        stats: _,
-        child,
-    } = init_state
-    {
+    if let ItemState::Exists { child, .. } = init_state {
         let child = child.unwrap();
 
         assert_eq!(
             child.scroll(),
-            &Scroll::new(
+            Some(&Scroll::new(
                 0.3,
                 true,
                 1_300_000.0,
                 Stats::from_vec(vec![5, 3, 0, 1])
-            )
+            ))
         );
         assert_eq!(child.p_goal, 0.186_686_606_25);
-        assert_eq!(child.exp_cost, 2_871_256.933_75);
+        assert_eq!(child.exp_cost, 2_870_769.178_75);
+    }
+}
+
+#[test]
+fn toy_of_101_cost_test() {
+    use crate::{
+        dfs::{solve_cost, solve_p},
+        goal::Goal,
+        graph::ItemState,
+        scroll::Scroll,
+        stats::Stats,
+    };
+
+    let scrolls = toy_of_101_scrolls();
+    let goal = Goal::from(Stats::from_vec(vec![108, 0, 0, 0]));
+
+    let mut init_state =
+        ItemState::new_exists(7, Stats::from_vec(vec![96, 3, 3, 0]));
+    let cost_per_goal = solve_cost(&mut init_state, &scrolls, &goal, &[]);
+
+    // The strategy that maximises the probability of reaching the goal is
+    // one of the candidates, so the minimal ratio can't be any worse.
+    let mut likeliest =
+        ItemState::new_exists(7, Stats::from_vec(vec![96, 3, 3, 0]));
+    solve_p(&mut likeliest, &scrolls, &goal, &[]);
+
+    if let (
+        ItemState::Exists { child, .. },
+        ItemState::Exists {
+            child: likeliest_child,
+            ..
+        },
+    ) = (init_state, likeliest)
+    {
+        let (child, likeliest_child) =
+            (child.unwrap(), likeliest_child.unwrap());
+
+        assert_eq!(
+            child.scroll(),
+            Some(&Scroll::new(
+                0.7,
+                true,
+                45_000.0,
+                Stats::from_vec(vec![2, 1, 0, 0])
+            ))
+        );
+        assert_eq!(cost_per_goal, child.exp_cost / child.p_goal);
+        assert!(
+            cost_per_goal < likeliest_child.exp_cost / likeliest_child.p_goal
+        );
+    }
+
+    // With one slot, each scroll is its own strategy, so the optimum is easy
+    // to work out by hand: the 50% scroll costs 10 / 0.5 = 20 per goal item,
+    // whereas the 90% scroll, which `solve_p` would choose, costs 27 / 0.9 =
+    // 30.
+    let scrolls = [
+        Scroll::new(0.5, false, 10.0, Stats::from_vec(vec![1])),
+        Scroll::new(0.9, false, 27.0, Stats::from_vec(vec![1])),
+    ];
+    let mut init_state = ItemState::new_exists(1, Stats::from_vec(vec![0]));
+    let cost_per_goal = solve_cost(
+        &mut init_state,
+        &scrolls,
+        &Goal::from(Stats::from_vec(vec![1])),
+        &[],
+    );
+
+    assert!((cost_per_goal - 20.0).abs() < 1e-9);
+    if let ItemState::Exists { child, .. } = init_state {
+        assert_eq!(child.unwrap().scroll(), Some(&scrolls[0]));
+    }
+}
+
+#[test]
+fn toy_of_101_renewal_test() {
+    use crate::{
+        dfs::solve_renewal, goal::Goal, graph::ItemState, scroll::Scroll,
+        stats::Stats,
+    };
+
+    let mut init_state =
+        ItemState::new_exists(7, Stats::from_vec(vec![96, 3, 3, 0]));
+    let scrolls = toy_of_101_scrolls();
+    let item_cost = 100_000_000.0;
+
+    let total_cost = solve_renewal(
+        &mut init_state,
+        &scrolls,
+        &Goal::from(Stats::from_vec(vec![108, 0, 0, 0])),
+        item_cost,
+        &[],
+    );
+
+    if let ItemState::Exists { child, .. } = init_state {
+        let child = child.unwrap();
+
+        // The item is so expensive that booming it is out of the question.
+        assert_eq!(
+            child.scroll(),
+            Some(&Scroll::new(
+                0.6,
+                false,
+                40_000.0,
+                Stats::from_vec(vec![2, 1, 0, 0])
+            ))
+        );
+        assert_eq!(
+            total_cost,
+            (item_cost + child.exp_cost) / child.p_goal - item_cost
+        );
+    }
+}
+
+#[test]
+fn stop_test() {
+    use crate::{
+        dfs::solve_p,
+        goal::Goal,
+        graph::{Action, ItemState},
+        scroll::Scroll,
+        stats::Stats,
+    };
+
+    let scrolls = [
+        Scroll::new(0.6, false, 40_000.0, Stats::from_vec(vec![2, 1])),
+        Scroll::new(0.7, true, 45_000.0, Stats::from_vec(vec![2, 1])),
+    ];
+
+    // The goal has already been met, so there's no point in scrolling.
+    let mut met = ItemState::new_exists(3, Stats::from_vec(vec![100, 3]));
+    solve_p(
+        &mut met,
+        &scrolls,
+        &Goal::from(Stats::from_vec(vec![100, 0])),
+        &[],
+    );
+
+    if let ItemState::Exists { child, .. } = met {
+        let child = child.unwrap();
+
+        assert_eq!(child.action(), Action::Stop);
+        assert!(child.outcomes().is_empty());
+        assert_eq!(child.p_goal, 1.0);
+        assert_eq!(child.exp_cost, 0.0);
+    }
+
+    // The goal can no longer be reached, so there's no point in scrolling.
+    let mut unreachable =
+        ItemState::new_exists(3, Stats::from_vec(vec![100, 3]));
+    solve_p(
+        &mut unreachable,
+        &scrolls,
+        &Goal::from(Stats::from_vec(vec![107, 0])),
+        &[],
+    );
+
+    if let ItemState::Exists { child, .. } = unreachable {
+        let child = child.unwrap();
+
+        assert_eq!(child.action(), Action::Stop);
+        assert_eq!(child.p_goal, 0.0);
+        assert_eq!(child.exp_cost, 0.0);
+    }
+}
+
+#[test]
+fn toy_of_101_pareto_test() {
+    use crate::{
+        dfs::{solve_p, solve_pareto},
+        goal::Goal,
+        graph::{Action, ItemState},
+        objective::MaxP,
+        stats::Stats,
+    };
+
+    let mut init_state =
+        ItemState::new_exists(7, Stats::from_vec(vec![96, 3, 3, 0]));
+    let scrolls = toy_of_101_scrolls();
+    let goal = Goal::from(Stats::from_vec(vec![108, 0, 0, 0]));
+
+    let front = solve_pareto(&init_state, &scrolls, &MaxP::new(&goal), 0.01);
+
+    assert!(front.len() > 2);
+    for (cheaper, likelier) in front.iter().zip(front.iter().skip(1)) {
+        assert!(cheaper.p_goal < likelier.p_goal);
+        assert!(cheaper.exp_cost < likelier.exp_cost);
+    }
+
+    // The cheapest strategy is to not do anything at all.
+    assert_eq!(front[0].action(), Action::Stop);
+    assert_eq!(front[0].p_goal, 0.0);
+
+    // The likeliest strategy is the one that `solve_p` would choose.
+    solve_p(&mut init_state, &scrolls, &goal, &[]);
+
+    if let ItemState::Exists { child, .. } = init_state {
+        let child = child.unwrap();
+        let likeliest = front.last().unwrap();
+
+        assert_eq!(likeliest.scroll(), child.scroll());
+        assert_eq!(likeliest.p_goal, child.p_goal);
+        assert_eq!(likeliest.exp_cost, child.exp_cost);
+    }
+}
+
+#[test]
+fn toy_of_101_budget_test() {
+    use crate::{dfs::solve_p, goal::Goal, graph::ItemState, stats::Stats};
+
+    /// Checks that no node of the strategy tree rooted at `state` has a
+    /// negative budget, and that every scroll use deducts its cost from the
+    /// budget.
+    fn check_budgets(state: &ItemState) {
+        if let ItemState::Exists { budget, child, .. } = state {
+            assert!(*budget >= 0.0);
+
+            let child = child.as_ref().unwrap();
+            for outcome in child.outcomes() {
+                if let ItemState::Exists {
+                    budget: outcome_budget,
+                    ..
+                } = outcome
+                {
+                    assert_eq!(
+                        *outcome_budget,
+                        budget - child.scroll().unwrap().cost
+                    );
+                }
+
+                check_budgets(outcome);
+            }
+        }
+    }
+
+    let scrolls = toy_of_101_scrolls();
+    let goal = Goal::from(Stats::from_vec(vec![108, 0, 0, 0]));
+
+    let mut unlimited =
+        ItemState::new_exists(7, Stats::from_vec(vec![96, 3, 3, 0]));
+    solve_p(&mut unlimited, &scrolls, &goal, &[]);
+
+    let mut limited = ItemState::new_exists_with_budget(
+        7,
+        Stats::from_vec(vec![96, 3, 3, 0]),
+        250_000.0,
+    );
+    solve_p(&mut limited, &scrolls, &goal, &[]);
+
+    check_budgets(&limited);
+
+    if let (
+        ItemState::Exists {
+            child: unlimited_child,
+            ..
+        },
+        ItemState::Exists {
+            child: limited_child,
+            ..
+        },
+    ) = (unlimited, limited)
+    {
+        let (unlimited_child, limited_child) =
+            (unlimited_child.unwrap(), limited_child.unwrap());
+
+        assert!(limited_child.p_goal > 0.0);
+        assert!(limited_child.p_goal < unlimited_child.p_goal);
+        assert!(limited_child.exp_cost <= 250_000.0);
+    }
+}
+
+#[test]
+fn toy_of_101_profit_test() {
+    use crate::{
+        dfs::solve_profit,
+        goal::Goal,
+        graph::{Action, ItemState},
+        stats::Stats,
+    };
+
+    let scrolls = toy_of_101_scrolls();
+    let goal = Goal::from(Stats::from_vec(vec![108, 0, 0, 0]));
+
+    // A 108 WATK Toy of 101 isn't worth scrolling for, if it only sells for
+    // 100k.
+    let mut cheap =
+        ItemState::new_exists(7, Stats::from_vec(vec![96, 3, 3, 0]));
+    let profit = solve_profit(&mut cheap, &scrolls, &goal, 100_000.0, &[]);
+
+    assert_eq!(profit, 0.0);
+    if let ItemState::Exists { child, .. } = cheap {
+        assert_eq!(child.unwrap().action(), Action::Stop);
+    }
+
+    // ...But it is if it sells for 5m.
+    let mut pricey =
+        ItemState::new_exists(7, Stats::from_vec(vec![96, 3, 3, 0]));
+    let profit = solve_profit(&mut pricey, &scrolls, &goal, 5_000_000.0, &[]);
+
+    assert!(profit > 0.0);
+    if let ItemState::Exists { child, .. } = pricey {
+        let child = child.unwrap();
+
+        assert_ne!(child.action(), Action::Stop);
+        assert_eq!(profit, child.exp_reward - child.exp_cost);
+        assert!((child.exp_reward - 5_000_000.0 * child.p_goal).abs() < 1e-6);
+    }
+}
+
+#[test]
+fn toy_of_101_reward_test() {
+    use crate::{
+        dfs::{solve_profit, solve_reward},
+        goal::Goal,
+        graph::ItemState,
+        reward::RewardTable,
+        stats::Stats,
+    };
+
+    let scrolls = toy_of_101_scrolls();
+
+    let rewards = RewardTable::from_vec(vec![
+        (Stats::from_vec(vec![105, 0, 0, 0]), 5_000_000.0),
+        (Stats::from_vec(vec![108, 0, 0, 0]), 20_000_000.0),
+        (Stats::from_vec(vec![110, 0, 0, 0]), 80_000_000.0),
+    ]);
+    assert_eq!(rewards.reward(&Stats::from_vec(vec![104, 9, 3, 1])), 0.0);
+    assert_eq!(
+        rewards.reward(&Stats::from_vec(vec![109, 9, 3, 1])),
+        20_000_000.0,
+    );
+
+    let mut init_state =
+        ItemState::new_exists(7, Stats::from_vec(vec![96, 3, 3, 0]));
+    let net = solve_reward(&mut init_state, &scrolls, &rewards, &[]);
+
+    if let ItemState::Exists { child, .. } = init_state {
+        let child = child.unwrap();
+
+        assert!(net > 0.0);
+        assert_eq!(net, child.exp_reward - child.exp_cost);
+        assert!(child.exp_reward <= 80_000_000.0 * child.p_goal);
+        assert!(child.exp_reward >= 5_000_000.0 * child.p_goal);
+    }
+
+    // A single tier is the same thing as a goal with a value.
+    let goal = Goal::from(Stats::from_vec(vec![108, 0, 0, 0]));
+    let mut init_state =
+        ItemState::new_exists(7, Stats::from_vec(vec![96, 3, 3, 0]));
+    let net = solve_reward(
+        &mut init_state,
+        &scrolls,
+        &RewardTable::from_vec(vec![(
+            Stats::from_vec(vec![108, 0, 0, 0]),
+            5_000_000.0,
+        )]),
+        &[],
+    );
+    let mut init_state =
+        ItemState::new_exists(7, Stats::from_vec(vec![96, 3, 3, 0]));
+    let profit =
+        solve_profit(&mut init_state, &scrolls, &goal, 5_000_000.0, &[]);
+
+    assert!((net - profit).abs() < 1e-6);
+}
+
+#[test]
+fn toy_of_101_custom_objective_test() {
+    use crate::{
+        dfs::{solve, solve_p},
+        goal::Goal,
+        graph::{ItemState, ScrollUse},
+        objective::Objective,
+        stats::Stats,
+    };
+
+    /// Maximises the probability of reaching `goal`, but only among the
+    /// strategies that are least likely to boom the item. The probability of
+    /// booming is stored as `exp_reward`.
+    struct Cautious {
+        goal: Goal,
+    }
+
+    impl Objective for Cautious {
+        fn leaf(&self, stats: &Stats, stop: &mut ScrollUse<'_>) {
+            stop.p_goal = if self.goal.is_met(stats) { 1.0 } else { 0.0 };
+        }
+
+        fn accumulate(
+            &self,
+            scroll_use: &mut ScrollUse<'_>,
+            p_outcome: f64,
+            outcome: Option<&ScrollUse<'_>>,
+        ) {
+            if let Some(outcome) = outcome {
+                scroll_use.p_goal += p_outcome * outcome.p_goal;
+                scroll_use.exp_cost += p_outcome * outcome.exp_cost;
+                scroll_use.exp_reward += p_outcome * outcome.exp_reward;
+            } else {
+                scroll_use.exp_reward += p_outcome;
+            }
+        }
+
+        fn prefers(
+            &self,
+            scroll_use: &ScrollUse<'_>,
+            incumbent: &ScrollUse<'_>,
+        ) -> bool {
+            scroll_use.exp_reward < incumbent.exp_reward
+                || (scroll_use.exp_reward <= incumbent.exp_reward
+                    && scroll_use.p_goal > incumbent.p_goal)
+        }
+    }
+
+    /// Checks that no node of the strategy tree rooted at `state` uses a dark
+    /// scroll.
+    fn check_no_dark(state: &ItemState) {
+        if let ItemState::Exists { child, .. } = state {
+            let child = child.as_ref().unwrap();
+            assert!(!child.scroll().is_some_and(|scroll| scroll.is_dark()));
+
+            for outcome in child.outcomes() {
+                check_no_dark(outcome);
+            }
+        }
+    }
+
+    let scrolls = toy_of_101_scrolls();
+    let goal = Goal::from(Stats::from_vec(vec![108, 0, 0, 0]));
+
+    let mut cautious =
+        ItemState::new_exists(7, Stats::from_vec(vec![96, 3, 3, 0]));
+    let cautious_child =
+        solve(&mut cautious, &scrolls, &Cautious { goal: goal.clone() })
+            .unwrap();
+
+    check_no_dark(&cautious);
+    assert_eq!(cautious_child.exp_reward, 0.0);
+
+    let mut reckless =
+        ItemState::new_exists(7, Stats::from_vec(vec![96, 3, 3, 0]));
+    solve_p(&mut reckless, &scrolls, &goal, &[]);
+
+    if let ItemState::Exists { child, .. } = reckless {
+        let reckless_child = child.unwrap();
+
+        assert!(cautious_child.p_goal > 0.0);
+        assert!(cautious_child.p_goal < reckless_child.p_goal);
+    }
+}
+
+#[test]
+fn toy_of_101_disjunctive_goal_test() {
+    use crate::{dfs::solve_p, goal::Goal, graph::ItemState, stats::Stats};
+
+    let scrolls = toy_of_101_scrolls();
+    let watk = Stats::from_vec(vec![108, 0, 0, 0]);
+    let mixed = Stats::from_vec(vec![104, 0, 0, 2]);
+
+    let p_goal = |goal: &Goal| {
+        let mut init_state =
+            ItemState::new_exists(7, Stats::from_vec(vec![96, 3, 3, 0]));
+        solve_p(&mut init_state, &scrolls, goal, &[]);
+
+        if let ItemState::Exists { child, .. } = init_state {
+            child.unwrap().p_goal
+        } else {
+            unreachable!()
+        }
+    };
+
+    let p_watk = p_goal(&Goal::from(watk.clone()));
+    let p_mixed = p_goal(&Goal::from(mixed.clone()));
+    let p_either = p_goal(&Goal::from_vec(vec![watk, mixed]));
+
+    assert!(p_mixed > 0.0);
+    assert!(p_either > p_watk);
+    assert!(p_either > p_mixed);
+    assert!(p_either <= p_watk + p_mixed);
+
+    // One alternative being out of reach doesn't stop the other from being
+    // reached.
+    let p_one_unreachable = p_goal(&Goal::from_vec(vec![
+        Stats::from_vec(vec![200, 0, 0, 0]),
+        Stats::from_vec(vec![108, 0, 0, 0]),
+    ]));
+
+    assert_eq!(p_one_unreachable, p_watk);
+}
+
+#[test]
+fn goal_parse_test() {
+    use crate::{goal::Goal, stats::Stats};
+
+    let names = ["WATK", "STR", "DEX", "LUK"];
+    let parse = |src| Goal::parse(src, &names).unwrap();
+
+    let goal = parse("WATK >= 108 || (WATK >= 106 && STR >= 10)");
+    assert!(goal.is_met(&Stats::from_vec(vec![108, 0, 0, 0])));
+    assert!(goal.is_met(&Stats::from_vec(vec![106, 10, 0, 0])));
+    assert!(!goal.is_met(&Stats::from_vec(vec![107, 9, 0, 0])));
+
+    let goal = parse("WATK + STR / 2 >= 110");
+    assert!(goal.is_met(&Stats::from_vec(vec![105, 10, 0, 0])));
+    assert!(!goal.is_met(&Stats::from_vec(vec![105, 9, 0, 0])));
+
+    let goal = parse("100 <= WATK <= 105 and not (LUK > 0)");
+    assert!(goal.is_met(&Stats::from_vec(vec![105, 9, 9, 0])));
+    assert!(!goal.is_met(&Stats::from_vec(vec![106, 0, 0, 0])));
+    assert!(!goal.is_met(&Stats::from_vec(vec![105, 0, 0, 1])));
+
+    let goal = parse("(WATK - 100) * 2 = -(DEX - 10)");
+    assert!(goal.is_met(&Stats::from_vec(vec![103, 0, 4, 0])));
+    assert!(!goal.is_met(&Stats::from_vec(vec![103, 0, 5, 0])));
+
+    let err = Goal::parse("WATK >= 108 || MATK >= 5", &names).unwrap_err();
+    assert_eq!(err.position(), 15);
+    assert!(Goal::parse("WATK * STR >= 5", &names).is_err());
+    assert!(Goal::parse("WATK / 0 >= 5", &names).is_err());
+    assert!(Goal::parse("WATK + 1", &names).is_err());
+    assert!(Goal::parse("(WATK >= 5", &names).is_err());
+    assert!(Goal::parse("WATK >= 5)", &names).is_err());
+    assert!(Goal::parse("WATK >= 5 $", &names).is_err());
+}
+
+#[test]
+fn toy_of_101_goal_expression_test() {
+    use crate::{
+        dfs::{solve, solve_p},
+        goal::Goal,
+        graph::{ItemState, ScrollUse},
+        objective::{MaxP, Objective},
+        stats::Stats,
+    };
+
+    /// `MaxP`, but without pruning anything.
+    struct Exhaustive<'g>(MaxP<'g>);
+
+    impl Objective for Exhaustive<'_> {
+        fn leaf(&self, stats: &Stats, stop: &mut ScrollUse<'_>) {
+            self.0.leaf(stats, stop);
+        }
+
+        fn prefers(
+            &self,
+            scroll_use: &ScrollUse<'_>,
+            incumbent: &ScrollUse<'_>,
+        ) -> bool {
+            self.0.prefers(scroll_use, incumbent)
+        }
+    }
+
+    let scrolls = toy_of_101_scrolls();
+    let names = ["WATK", "STR", "DEX", "LUK"];
+
+    let p_goal = |goal: &Goal| {
+        let mut init_state =
+            ItemState::new_exists(7, Stats::from_vec(vec![96, 3, 3, 0]));
+        solve_p(&mut init_state, &scrolls, goal, &[]);
+
+        if let ItemState::Exists { child, .. } = init_state {
+            child.unwrap().p_goal
+        } else {
+            unreachable!()
+        }
+    };
+
+    // A parsed goal behaves just like the equivalent goal built directly.
+    assert_eq!(
+        p_goal(
+            &Goal::parse("WATK >= 108 || WATK >= 104 && LUK >= 2", &names)
+                .unwrap()
+        ),
+        p_goal(&Goal::from_vec(vec![
+            Stats::from_vec(vec![108, 0, 0, 0]),
+            Stats::from_vec(vec![104, 0, 0, 2]),
+        ])),
+    );
+
+    // Pruning never throws away anything of value, even when the goal isn't
+    // just a matter of stats being high enough.
+    for src in &[
+        "WATK + STR / 2 >= 110",
+        "106 <= WATK <= 108",
+        "WATK >= 105 && LUK = 0",
+        "!(WATK < 104 || STR > 12)",
+    ] {
+        let goal = Goal::parse(src, &names).unwrap();
+
+        let mut init_state =
+            ItemState::new_exists(7, Stats::from_vec(vec![96, 3, 3, 0]));
+        let exhaustive =
+            solve(&mut init_state, &scrolls, &Exhaustive(MaxP::new(&goal)))
+                .unwrap();
+
+        let p_goal = p_goal(&goal);
+        assert!(p_goal > 0.0);
+        assert!((p_goal - exhaustive.p_goal).abs() < 1e-12);
+    }
+}
+
+#[test]
+fn lexicographic_test() {
+    use crate::{
+        dfs::{solve_lexicographic, solve_p},
+        goal::Goal,
+        graph::{Action, ItemState},
+        scroll::Scroll,
+        stats::Stats,
+    };
+
+    // The first goal is out of reach, so `solve_p` just stops, but the
+    // fallback goal is still worth going for.
+    let scrolls = [
+        Scroll::new(1.0, false, 10.0, Stats::from_vec(vec![1])),
+        Scroll::new(0.5, false, 20.0, Stats::from_vec(vec![2])),
+    ];
+    let goals = [
+        Goal::from(Stats::from_vec(vec![10])),
+        Goal::from(Stats::from_vec(vec![2])),
+    ];
+
+    let mut init_state = ItemState::new_exists(1, Stats::from_vec(vec![0]));
+    solve_p(&mut init_state, &scrolls, &goals[0], &[]);
+
+    if let ItemState::Exists { child, .. } = init_state {
+        assert_eq!(child.unwrap().action(), Action::Stop);
+    }
+
+    let mut init_state = ItemState::new_exists(1, Stats::from_vec(vec![0]));
+    solve_lexicographic(&mut init_state, &scrolls, &goals, &[]);
+
+    if let ItemState::Exists { child, .. } = init_state {
+        let child = child.unwrap();
+
+        assert_eq!(child.action(), Action::Scroll(&scrolls[1]));
+        assert_eq!(child.p_goals, vec![0.0, 0.5]);
+    }
+
+    // Fallback goals never come at the expense of the first goal.
+    let scrolls = toy_of_101_scrolls();
+    let goals = [
+        Goal::from(Stats::from_vec(vec![110, 0, 0, 0])),
+        Goal::from(Stats::from_vec(vec![108, 0, 0, 0])),
+        Goal::from(Stats::from_vec(vec![105, 0, 0, 0])),
+    ];
+
+    let mut init_state =
+        ItemState::new_exists(7, Stats::from_vec(vec![96, 3, 3, 0]));
+    solve_p(&mut init_state, &scrolls, &goals[0], &[]);
+    let p_first = if let ItemState::Exists { child, .. } = init_state {
+        child.unwrap().p_goal
+    } else {
+        unreachable!()
+    };
+
+    let mut init_state =
+        ItemState::new_exists(7, Stats::from_vec(vec![96, 3, 3, 0]));
+    solve_lexicographic(&mut init_state, &scrolls, &goals, &[]);
+
+    if let ItemState::Exists { child, .. } = init_state {
+        let child = child.unwrap();
+
+        assert_eq!(child.p_goals.len(), 3);
+        assert_eq!(child.p_goal, child.p_goals[0]);
+        assert!((child.p_goals[0] - p_first).abs() < 1e-12);
+        assert!(child.p_goals[0] <= child.p_goals[1]);
+        assert!(child.p_goals[1] <= child.p_goals[2]);
+    }
+}
+
+#[test]
+fn chaos_test() {
+    use crate::{
+        dfs::solve_p,
+        goal::Goal,
+        graph::ItemState,
+        scroll::Scroll,
+        stats::{StatDelta, Stats},
+    };
+
+    let names = ["WATK"];
+    let chaos = [Scroll::new_chaos(
+        1.0,
+        false,
+        10.0,
+        vec![
+            (0.5, StatDelta::from_vec(vec![-5])),
+            (0.5, StatDelta::from_vec(vec![5])),
+        ],
+    )];
+
+    let solve = |stats: u16, goal: &str| {
+        let mut init_state =
+            ItemState::new_exists(1, Stats::from_vec(vec![stats]));
+        solve_p(
+            &mut init_state,
+            &chaos,
+            &Goal::parse(goal, &names).unwrap(),
+            &[],
+        );
+
+        if let ItemState::Exists { child, .. } = init_state {
+            child.unwrap()
+        } else {
+            unreachable!()
+        }
+    };
+
+    // The bad outcome can't reach the goal, so it's left out.
+    let child = solve(10, "WATK >= 12");
+    assert_eq!(child.p_goal, 0.5);
+    assert_eq!(child.p_outcomes(), &[0.5]);
+
+    // Both outcomes are explored when stats going down can be a good thing.
+    let child = solve(10, "WATK <= 7");
+    assert_eq!(child.p_goal, 0.5);
+    assert_eq!(child.p_outcomes(), &[0.5]);
+    let child = solve(10, "WATK != 10");
+    assert_eq!(child.p_goal, 1.0);
+    assert_eq!(child.p_outcomes(), &[0.5, 0.5]);
+
+    // Stats can't go below zero.
+    let child = solve(3, "WATK = 0");
+    assert_eq!(child.p_goal, 0.5);
+    if let ItemState::Exists { stats, .. } = &child.outcomes()[0] {
+        assert_eq!(stats, &Stats::from_vec(vec![0]));
+    }
+
+    // Adding a Chaos Scroll to the usual scrolls can only help.
+    let mut scrolls = toy_of_101_scrolls().to_vec();
+    let goal = Goal::from(Stats::from_vec(vec![111, 0, 0, 0]));
+
+    let mut init_state =
+        ItemState::new_exists(7, Stats::from_vec(vec![96, 3, 3, 0]));
+    solve_p(&mut init_state, &scrolls, &goal, &[]);
+    let p_without = if let ItemState::Exists { child, .. } = init_state {
+        child.unwrap().p_goal
+    } else {
+        unreachable!()
+    };
+
+    scrolls.push(Scroll::new_chaos(
+        0.6,
+        false,
+        2_000_000.0,
+        (-5..=5)
+            .map(|d| (1.0 / 11.0, StatDelta::from_vec(vec![d, d, d, d])))
+            .collect(),
+    ));
+    let mut init_state =
+        ItemState::new_exists(7, Stats::from_vec(vec![96, 3, 3, 0]));
+    solve_p(&mut init_state, &scrolls, &goal, &[]);
+
+    if let ItemState::Exists { child, .. } = init_state {
+        let child = child.unwrap();
+
+        assert!(child.p_goal >= p_without);
+        assert!(child.p_outcomes().iter().sum::<f64>() <= 1.0 + 1e-12);
+    }
+}
+
+#[test]
+fn signed_stats_test() {
+    use crate::{
+        dfs::solve_p,
+        goal::Goal,
+        graph::ItemState,
+        scroll::Scroll,
+        stats::{StatDelta, Stats},
+    };
+
+    // Stats saturate rather than overflowing, and never go below zero.
+    assert_eq!(
+        Stats::from_vec(vec![65_530, 1]).plus(&Stats::from_vec(vec![10, 1])),
+        Stats::from_vec(vec![u16::MAX, 2]),
+    );
+    assert_eq!(
+        Stats::from_vec(vec![65_530, 1, 4])
+            .plus_delta(&StatDelta::from_vec(vec![10, -2, -4])),
+        Stats::from_vec(vec![u16::MAX, 0, 0]),
+    );
+    assert_eq!(
+        Stats::from_vec(vec![30_000]) * 3,
+        Stats::from_vec(vec![u16::MAX]),
+    );
+
+    // A dark scroll that trades DEX for WATK, alongside one that doesn't.
+    let names = ["WATK", "DEX"];
+    let scrolls = [
+        Scroll::new(0.7, true, 45_000.0, StatDelta::from_vec(vec![3, -1])),
+        Scroll::new(0.6, false, 40_000.0, Stats::from_vec(vec![1, 0])),
+    ];
+
+    let solve = |goal: &str| {
+        let mut init_state =
+            ItemState::new_exists(3, Stats::from_vec(vec![10, 2]));
+        solve_p(
+            &mut init_state,
+            &scrolls,
+            &Goal::parse(goal, &names).unwrap(),
+            &[],
+        );
+
+        if let ItemState::Exists { child, .. } = init_state {
+            child.unwrap()
+        } else {
+            unreachable!()
+        }
+    };
+
+    // When DEX doesn't matter, the dark scroll is the way to go.
+    let child = solve("WATK >= 16");
+    assert_eq!(child.scroll(), Some(&scrolls[0]));
+    if let ItemState::Exists { stats, .. } = &child.outcomes()[0] {
+        assert_eq!(stats, &Stats::from_vec(vec![13, 1]));
+    }
+
+    // When it does, the dark scroll can only be used once.
+    let child = solve("WATK >= 14 && DEX >= 1");
+    assert!(child.p_goal > 0.0);
+    assert!(child.p_goal < solve("WATK >= 14").p_goal);
+    assert_eq!(solve("WATK >= 16 && DEX >= 1").p_goal, 0.0);
+}
+
+#[test]
+fn modifiers_test() {
+    use crate::{
+        graph::ItemState,
+        scroll::{Modifier, Scroll},
+        stats::Stats,
+    };
+
+    let init_state =
+        |slots| ItemState::new_exists(slots, Stats::from_vec(vec![0]));
+
+    let scroll = Scroll::new(0.5, false, 10.0, Stats::from_vec(vec![1]));
+
+    // With a White Scroll, the scroll is just used until it succeeds.
+    let scrolls = Scroll::combinations(
+        std::slice::from_ref(&scroll),
+        &[Modifier::white_scroll(5.0)],
+    );
+    assert_eq!(scrolls.len(), 2);
+    let child = solve_watk(&scrolls, init_state(1), 1);
+    assert_eq!(child.scroll(), Some(&scrolls[1]));
+    assert!((child.p_goal - 1.0).abs() < 1e-12);
+    assert!((child.exp_cost - 30.0).abs() < 1e-9);
+    assert_eq!(child.p_outcomes(), &[1.0]);
+
+    // ...but only as many times as the budget allows. With a budget of 100,
+    // that's six uses with a White Scroll, followed by one without.
+    let child = solve_watk(
+        &scrolls,
+        ItemState::new_exists_with_budget(1, Stats::from_vec(vec![0]), 100.0),
+        1,
+    );
+    assert!((child.p_goal - (1.0 - 0.5f64.powi(7))).abs() < 1e-12);
+
+    // A Lucky Day scroll raises the probability of success.
+    let scrolls = [scroll.with_modifier(&Modifier::lucky_day(5.0, 0.1))];
+    let child = solve_watk(&scrolls, init_state(1), 1);
+    assert!((child.p_goal - 0.6).abs() < 1e-12);
+
+    // A Protect Shield keeps a dark scroll from booming the item.
+    let dark = Scroll::new(0.3, true, 10.0, Stats::from_vec(vec![1]));
+    let shielded = dark.with_modifier(&Modifier::protect_shield(5.0));
+    assert_eq!(shielded.p_boom, 0.0);
+    assert_eq!(shielded.cost, 15.0);
+    let scrolls = [shielded];
+    let child = solve_watk(&scrolls, init_state(2), 1);
+    assert!((child.p_goal - 0.51).abs() < 1e-12);
+    assert!(child
+        .outcomes()
+        .iter()
+        .all(|outcome| matches!(outcome, ItemState::Exists { .. })));
+}
+
+#[test]
+fn clean_slate_test() {
+    use crate::{
+        dfs::{solve_pareto, solve_renewal},
+        goal::Goal,
+        graph::ItemState,
+        objective::MaxP,
+        scroll::Scroll,
+        stats::Stats,
+    };
+
+    let init_state = |budget| {
+        ItemState::new_exists_with_budget(1, Stats::from_vec(vec![0]), budget)
+    };
+
+    let scroll = Scroll::new(0.5, false, 10.0, Stats::from_vec(vec![1]));
+    let css = Scroll::new_clean_slate(0.5, false, 5.0);
+
+    // Without a budget, the slot can be recovered as many times as it takes.
+    let scrolls = [scroll.clone(), css];
+    let child = solve_watk(&scrolls, init_state(f64::INFINITY), 1);
+    assert_eq!(child.scroll(), Some(&scrolls[0]));
+    assert!((child.p_goal - 1.0).abs() < 1e-9);
+    assert!((child.exp_cost - 30.0).abs() < 1e-6);
+    if let ItemState::Exists {
+        slots,
+        failed_slots,
+        child,
+        ..
+    } = &child.outcomes()[1]
+    {
+        assert_eq!((*slots, *failed_slots), (0, 1));
+        assert_eq!(child.as_ref().unwrap().scroll(), Some(&scrolls[1]));
+    } else {
+        panic!("the failure of the scroll should be its second outcome");
+    }
+
+    // The Pareto front goes all the way up, too.
+    let front = solve_pareto(
+        &ItemState::new_exists(1, Stats::from_vec(vec![0])),
+        &scrolls,
+        &MaxP::new(&Goal::from(Stats::from_vec(vec![1]))),
+        0.01,
+    );
+    assert!((front.last().unwrap().p_goal - 1.0).abs() < 1e-9);
+    assert!((front.last().unwrap().exp_cost - 30.0).abs() < 1e-6);
+
+    // With a budget, it can't.
+    let p_without = solve_watk(
+        std::slice::from_ref(&scroll),
+        init_state(f64::INFINITY),
+        1,
+    )
+    .p_goal;
+    assert!((p_without - 0.5).abs() < 1e-12);
+    let p_budget = solve_watk(&scrolls, init_state(40.0), 1).p_goal;
+    assert!(p_budget > p_without && p_budget < 1.0 - 1e-9);
+
+    // An item that has already lost its only slot can still have it
+    // recovered.
+    let child = solve_watk(
+        &scrolls,
+        ItemState::new_exists(0, Stats::from_vec(vec![0]))
+            .with_failed_slots(1),
+        1,
+    );
+    assert_eq!(child.scroll(), Some(&scrolls[1]));
+    assert!((child.p_goal - 1.0).abs() < 1e-9);
+    assert!((child.exp_cost - 40.0).abs() < 1e-6);
+
+    // A dark Clean Slate Scroll might boom the item instead.
+    let scrolls = [scroll.clone(), Scroll::new_clean_slate(0.5, true, 5.0)];
+    let child = solve_watk(&scrolls, init_state(f64::INFINITY), 1);
+    assert!((child.p_goal - 0.75).abs() < 1e-9);
+
+    // When replacement items are expensive, recovering slots is worth it.
+    let scrolls = [scroll, Scroll::new_clean_slate(0.5, false, 5.0)];
+    let mut init_state = ItemState::new_exists(1, Stats::from_vec(vec![0]));
+    let renewal_cost = solve_renewal(
+        &mut init_state,
+        &scrolls,
+        &Goal::from(Stats::from_vec(vec![1])),
+        100.0,
+        &[],
+    );
+    assert!((renewal_cost - 30.0).abs() < 1e-6);
+}
+
+#[test]
+fn hammer_test() {
+    use crate::{
+        dfs::{solve_p, solve_profit},
+        goal::Goal,
+        graph::ItemState,
+        scroll::Scroll,
+        stats::Stats,
+    };
+
+    let scrolls = [
+        Scroll::new(1.0, false, 10.0, Stats::from_vec(vec![1])),
+        Scroll::new_hammer(0.5, 25.0),
+    ];
+    let init_state = ItemState::new_exists(0, Stats::from_vec(vec![0]));
+    let goal = |watk| Goal::from(Stats::from_vec(vec![watk]));
+
+    // Without any hammers, there's nothing to be done.
+    let mut state = init_state.clone();
+    solve_p(&mut state, &scrolls, &goal(1), &[]);
+    if let ItemState::Exists { child, .. } = state {
+        assert_eq!(child.unwrap().p_goal, 0.0);
+    }
+
+    // With one, the hammer is used until it succeeds, and then the scroll.
+    let mut state = init_state.clone().with_hammers(1);
+    solve_p(&mut state, &scrolls, &goal(1), &[]);
+    if let ItemState::Exists { child, .. } = state {
+        let child = child.unwrap();
+        assert_eq!(child.scroll(), Some(&scrolls[1]));
+        assert!((child.p_goal - 1.0).abs() < 1e-12);
+        assert!((child.exp_cost - 60.0).abs() < 1e-9);
+        if let ItemState::Exists {
+            slots,
+            hammers,
+            child,
+            ..
+        } = &child.outcomes()[0]
+        {
+            assert_eq!((*slots, *hammers), (1, 0));
+            assert_eq!(child.as_ref().unwrap().scroll(), Some(&scrolls[0]));
+        }
+    }
+
+    // Each hammer only adds the one slot.
+    let mut state = init_state.clone().with_hammers(1);
+    solve_p(&mut state, &scrolls, &goal(2), &[]);
+    if let ItemState::Exists { child, .. } = state {
+        assert_eq!(child.unwrap().p_goal, 0.0);
+    }
+
+    // Hammering is only worth it if the goal is worth more than it costs.
+    let mut state = init_state.clone().with_hammers(1);
+    assert_eq!(solve_profit(&mut state, &scrolls, &goal(1), 50.0, &[]), 0.0);
+    let mut state = init_state.with_hammers(1);
+    let profit = solve_profit(&mut state, &scrolls, &goal(1), 100.0, &[]);
+    assert!((profit - 40.0).abs() < 1e-9);
+}
+
+#[test]
+fn reset_test() {
+    use crate::{graph::ItemState, scroll::Scroll, stats::Stats};
+
+    let scroll = Scroll::new(0.5, false, 10.0, Stats::from_vec(vec![1]));
+    let innocence =
+        Scroll::new_reset(0.5, true, 5.0, 2, Stats::from_vec(vec![0]));
+    let clean = ItemState::new_exists(2, Stats::from_vec(vec![0]));
+
+    // Without resets, both scrolls have to succeed.
+    let p_without =
+        solve_watk(std::slice::from_ref(&scroll), clean.clone(), 2);
+    assert!((p_without.p_goal - 0.25).abs() < 1e-12);
+
+    // With them, a failure can be undone by starting over, but each attempt
+    // to start over has a chance of booming the item. Starting over succeeds
+    // with probability 2/3, so the probability of reaching the goal, p, is
+    // 1/4 + 3/4 * 2/3 * p.
+    let scrolls = [scroll, innocence];
+    let child = solve_watk(&scrolls, clean, 2);
+    assert_eq!(child.scroll(), Some(&scrolls[0]));
+    assert!((child.p_goal - 0.5).abs() < 1e-9);
+
+    // An item that's out of slots can still be reset, and the goal isn't out
+    // of reach just because the item's current stats can't get there.
+    let child = solve_watk(
+        &scrolls,
+        ItemState::new_exists(0, Stats::from_vec(vec![1])),
+        2,
+    );
+    assert_eq!(child.scroll(), Some(&scrolls[1]));
+    assert!((child.p_goal - 2.0 / 3.0 * 0.5).abs() < 1e-9);
+}
+
+#[test]
+fn outcome_distribution_test() {
+    use crate::{
+        graph::ItemState,
+        scroll::{Modifier, Scroll},
+        stats::{StatDelta, Stats},
+    };
+
+    // The old dark flag splits failures evenly between misses and booms.
+    let dark = Scroll::new(0.3, true, 10.0, Stats::from_vec(vec![1]));
+    assert!((dark.p_boom - 0.35).abs() < 1e-12);
+    assert!((dark.p_miss() - 0.35).abs() < 1e-12);
+    let light = Scroll::new(0.3, false, 10.0, Stats::from_vec(vec![1]));
+    assert!(!light.is_dark());
+
+    // A Lucky Day takes from both in proportion.
+    let lucky = dark.with_modifier(&Modifier::lucky_day(5.0, 0.1));
+    assert!((lucky.p_boom - 0.3).abs() < 1e-12);
+    assert!((lucky.p_miss() - 0.3).abs() < 1e-12);
+
+    let solve = |scroll: Scroll, slots| {
+        let scrolls = [scroll];
+        let child = solve_watk(
+            &scrolls,
+            ItemState::new_exists(slots, Stats::from_vec(vec![0])),
+            1,
+        );
+
+        (child.p_goal, child.p_outcomes().to_vec())
+    };
+
+    // A scroll that only rarely booms the item.
+    let (p_goal, p_outcomes) = solve(
+        Scroll::new_with_p_boom(
+            0.6,
+            0.05,
+            10.0,
+            vec![(1.0, StatDelta::from_vec(vec![1]).into())],
+        ),
+        2,
+    );
+    assert!((p_goal - (0.6 + 0.35 * 0.6)).abs() < 1e-12);
+    assert_eq!(p_outcomes.len(), 3);
+    assert!((p_outcomes[1] - 0.35).abs() < 1e-12);
+    assert!((p_outcomes[2] - 0.05).abs() < 1e-12);
+
+    // A dark scroll that booms the item on 90% of its failures.
+    let (p_goal, _) = solve(
+        Scroll::new_with_p_boom(
+            0.3,
+            0.63,
+            10.0,
+            vec![(1.0, StatDelta::from_vec(vec![1]).into())],
+        ),
+        2,
+    );
+    assert!((p_goal - (0.3 + 0.07 * 0.3)).abs() < 1e-12);
+}
+
+#[test]
+fn slot_delta_test() {
+    use crate::{
+        graph::ItemState,
+        scroll::{Scroll, Success},
+        stats::Stats,
+    };
+    use std::panic::{self, AssertUnwindSafe};
+
+    // A scroll that uses up two slots, whether it succeeds or misses.
+    let scrolls = [Scroll {
+        miss_slots: -2,
+        ..Scroll::new_with_p_boom(
+            0.5,
+            0.0,
+            10.0,
+            vec![(1.0, Success::new(Stats::from_vec(vec![3]), -2))],
+        )
+    }];
+    assert_eq!(scrolls[0].slots_needed(), 2);
+    let child = solve_watk(
+        &scrolls,
+        ItemState::new_exists(3, Stats::from_vec(vec![0])),
+        3,
+    );
+    assert!((child.p_goal - 0.5).abs() < 1e-12);
+    for outcome in child.outcomes() {
+        if let ItemState::Exists {
+            slots,
+            failed_slots,
+            child,
+            ..
+        } = outcome
+        {
+            // Without a Clean Slate Scroll or a success rate, there's no
+            // point in keeping track of the slots lost to the miss.
+            assert_eq!((*slots, *failed_slots), (1, 0));
+
+            // There's only one slot left, which isn't enough.
+            assert_eq!(child.as_ref().unwrap().scroll(), None);
+        }
+    }
+
+    // A scroll that grants a slot on success, alongside one that grants
+    // stats. The budget keeps the search from going on forever.
+    let scrolls = [
+        Scroll::new(1.0, false, 10.0, Stats::from_vec(vec![1])),
+        Scroll::new_with_p_boom(
+            0.5,
+            0.0,
+            10.0,
+            vec![(1.0, Success::new(Stats::from_vec(vec![0]), 1))],
+        ),
+    ];
+    let init_state =
+        ItemState::new_exists_with_budget(1, Stats::from_vec(vec![0]), 30.0);
+    let child = solve_watk(&scrolls[..1], init_state.clone(), 2);
+    assert_eq!(child.p_goal, 0.0);
+    let child = solve_watk(&scrolls, init_state, 2);
+    assert_eq!(child.scroll(), Some(&scrolls[1]));
+    assert!((child.p_goal - 0.5).abs() < 1e-12);
+
+    // Without a budget, there's no end to how many scrolls could be used,
+    // whether a success uses up no slots, or grants one, so rather than
+    // searching forever, the search refuses to start.
+    let slotless = [Scroll::new_with_p_boom(
+        0.5,
+        0.0,
+        10.0,
+        vec![(1.0, Success::new(Stats::from_vec(vec![1]), 0))],
+    )];
+    for scrolls in [&slotless[..], &scrolls[..]].iter() {
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            solve_watk(
+                scrolls,
+                ItemState::new_exists(2, Stats::from_vec(vec![0])),
+                3,
+            )
+            .p_goal
+        }));
+        assert!(result.is_err());
+    }
+}
+
+#[test]
+fn success_rate_test() {
+    use crate::{
+        graph::ItemState,
+        scroll::{ItemContext, Scroll, SuccessRate},
+        stats::Stats,
+    };
+
+    let init_state = ItemState::new_exists(2, Stats::from_vec(vec![0]));
+
+    // Without a success rate, misses in a row aren't kept track of.
+    let scrolls = [Scroll::new(0.1, false, 10.0, Stats::from_vec(vec![1]))];
+    let child = solve_watk(&scrolls, init_state.clone(), 1);
+    assert!((child.p_goal - 0.19).abs() < 1e-12);
+    for outcome in child.outcomes() {
+        if let ItemState::Exists { streak, .. } = outcome {
+            assert_eq!(*streak, 0);
+        }
+    }
+
+    // Each miss in a row adds 30% to the probability of success.
+    let pity =
+        SuccessRate::new(|p_suc, item| p_suc + 0.3 * f64::from(item.streak));
+    let scrolls = [scrolls[0].clone().with_success_rate(pity.clone())];
+    let child = solve_watk(&scrolls, init_state.clone(), 1);
+    assert!((child.p_goal - 0.46).abs() < 1e-12);
+    for outcome in child.outcomes() {
+        if let ItemState::Exists { stats, streak, .. } = outcome {
+            let missed = stats == &Stats::from_vec(vec![0]);
+            assert_eq!(*streak, u8::from(missed));
+        }
+    }
+    let child = solve_watk(&scrolls, init_state.clone().with_streak(3), 1);
+    assert!((child.p_goal - 1.0).abs() < 1e-12);
+
+    // Each upgrade takes 20% away from the probability of success.
+    let scrolls = [Scroll::new(0.6, false, 10.0, Stats::from_vec(vec![1]))
+        .with_success_rate(SuccessRate::new(|p_suc, item| {
+            p_suc - 0.2 * f64::from(item.upgrades)
+        }))];
+    let child = solve_watk(&scrolls, init_state.clone(), 2);
+    assert!((child.p_goal - 0.24).abs() < 1e-12);
+
+    // A miss isn't an upgrade, so a success after it is just as likely as
+    // the first scroll was to succeed.
+    let child = solve_watk(&scrolls, init_state.clone(), 1);
+    assert!((child.p_goal - (0.6 + 0.4 * 0.6)).abs() < 1e-12);
+    let child = solve_watk(&scrolls, init_state.with_upgrades(1), 1);
+    assert!((child.p_goal - (0.4 + 0.6 * 0.4)).abs() < 1e-12);
+
+    // The probability of failure is split between booming and missing as
+    // usual.
+    let scroll = Scroll::new(0.5, true, 10.0, Stats::from_vec(vec![1]))
+        .with_success_rate(SuccessRate::new(|_, _| 0.8));
+    let stats = Stats::from_vec(vec![0]);
+    let item = ItemContext {
+        slots: 1,
+        failed_slots: 0,
+        hammers: 0,
+        streak: 0,
+        upgrades: 0,
+        stats: &stats,
+    };
+    let (p_suc, p_boom, p_miss) = scroll.probabilities(&item);
+    assert!((p_suc - 0.8).abs() < 1e-12);
+    assert!((p_boom - 0.1).abs() < 1e-12);
+    assert!((p_miss - 0.1).abs() < 1e-12);
+    assert_eq!(scroll, scroll.clone());
+    assert_ne!(scroll, scroll.clone().with_success_rate(pity));
+}
+
+#[test]
+fn event_test() {
+    use crate::{
+        dfs::solve_event,
+        goal::Goal,
+        graph::ItemState,
+        objective::MaxP,
+        scroll::{Event, Scroll, ScrollKind},
+        stats::Stats,
+    };
+
+    let init_state = ItemState::new_exists(1, Stats::from_vec(vec![0]));
+    let scrolls = [Scroll::new(0.1, false, 10.0, Stats::from_vec(vec![1]))];
+    let goal = Goal::from(Stats::from_vec(vec![1]));
+
+    for (event, p_goal) in [
+        (Event::new(1.0, 0.1), 0.2),
+        (Event::new(2.0, 0.0), 0.2),
+        (Event::new(1.0, 0.95), 1.0),
+        (
+            Event {
+                kinds: vec![ScrollKind::Hammer],
+                ..Event::new(1.0, 0.1)
+            },
+            0.1,
+        ),
+    ] {
+        let (p_without, p_with) =
+            solve_event(&init_state, &scrolls, &MaxP::new(&goal), &event);
+        assert!((p_without - 0.1).abs() < 1e-12);
+        assert!((p_with - p_goal).abs() < 1e-12);
+    }
+
+    // The increase comes out of the probabilities of missing and of booming
+    // in proportion to their size.
+    let scroll = Event::new(1.0, 0.2).scroll(&Scroll::new(
+        0.5,
+        true,
+        10.0,
+        Stats::from_vec(vec![1]),
+    ));
+    assert!((scroll.p_suc - 0.7).abs() < 1e-12);
+    assert!((scroll.p_boom - 0.15).abs() < 1e-12);
+    assert!((scroll.p_miss() - 0.15).abs() < 1e-12);
+
+    // An event for reset scrolls affects every one of them, whatever it
+    // resets the item to.
+    let event = Event {
+        kinds: vec![ScrollKind::Reset],
+        ..Event::new(1.0, 0.2)
+    };
+    let innocence =
+        Scroll::new_reset(0.5, false, 10.0, 2, Stats::from_vec(vec![0]));
+    assert_eq!(innocence.kind(), ScrollKind::Reset);
+    assert!((event.scroll(&innocence).p_suc - 0.7).abs() < 1e-12);
+    assert_eq!(event.scroll(&scrolls[0]), scrolls[0]);
+
+    // An event can also pick out scrolls by anything else about them, e.g.
+    // only dark scrolls.
+    let event = Event::new(1.0, 0.2).with_filter(Scroll::is_dark);
+    let dark = Scroll::new(0.3, true, 10.0, Stats::from_vec(vec![1]));
+    assert!((event.scroll(&dark).p_suc - 0.5).abs() < 1e-12);
+    assert_eq!(event.scroll(&scrolls[0]), scrolls[0]);
+    assert_eq!(event, event.clone());
+    assert_ne!(event, Event::new(1.0, 0.2).with_filter(Scroll::is_dark));
+}
+
+#[test]
+fn inventory_test() {
+    use crate::{
+        dfs::solve_p,
+        goal::Goal,
+        graph::ItemState,
+        scroll::{Modifier, Scroll},
+        stats::Stats,
+    };
+
+    // Three 30% scrolls, and as many 10% scrolls as we like.
+    let scrolls = [
+        Scroll::new(0.3, false, 10.0, Stats::from_vec(vec![1]))
+            .with_inventory(0),
+        Scroll::new(0.1, false, 10.0, Stats::from_vec(vec![1])),
+    ];
+    let goal = Goal::from(Stats::from_vec(vec![1]));
+
+    let mut state = ItemState::new_exists(5, Stats::from_vec(vec![0]))
+        .with_inventory(vec![3]);
+    solve_p(&mut state, &scrolls, &goal, &[]);
+    if let ItemState::Exists { child, .. } = state {
+        let child = child.unwrap();
+        let p_goal = 1.0 - 0.7f64.powi(3) * 0.9f64.powi(2);
+        assert!((child.p_goal - p_goal).abs() < 1e-12);
+        assert_eq!(child.scroll(), Some(&scrolls[0]));
+        for outcome in child.outcomes() {
+            if let ItemState::Exists { inventory, .. } = outcome {
+                assert_eq!(inventory, &[2]);
+            }
+        }
+    }
+
+    // None left at all.
+    let mut state = ItemState::new_exists(1, Stats::from_vec(vec![0]));
+    solve_p(&mut state, &scrolls, &goal, &[]);
+    if let ItemState::Exists { child, .. } = state {
+        let child = child.unwrap();
+        assert!((child.p_goal - 0.1).abs() < 1e-12);
+        assert_eq!(child.scroll(), Some(&scrolls[1]));
+    }
+
+    // The same scroll alongside a White Scroll shares the inventory, so a
+    // miss doesn't just mean using the scroll again.
+    let scrolls =
+        Scroll::combinations(&scrolls[..1], &[Modifier::white_scroll(10.0)]);
+    let mut state = ItemState::new_exists(1, Stats::from_vec(vec![0]))
+        .with_inventory(vec![2]);
+    solve_p(&mut state, &scrolls, &goal, &[]);
+    if let ItemState::Exists { child, .. } = state {
+        let child = child.unwrap();
+        assert!((child.p_goal - 0.51).abs() < 1e-12);
+        assert_eq!(child.scroll(), Some(&scrolls[1]));
+    }
+}
+
+#[test]
+fn pricing_test() {
+    use crate::{
+        dfs::solve_p,
+        goal::Goal,
+        graph::ItemState,
+        scroll::{Modifier, Pricing, Scroll},
+        stats::Stats,
+    };
+
+    let goal = Goal::from(Stats::from_vec(vec![1]));
+
+    // The first scroll is cheap, and the rest cost the usual price.
+    let scrolls = [Scroll::new(0.5, false, 10.0, Stats::from_vec(vec![1]))
+        .with_pricing(Pricing::new(0, vec![(1, 2.0)]))];
+    let mut state = ItemState::new_exists(2, Stats::from_vec(vec![0]));
+    solve_p(&mut state, &scrolls, &goal, &[]);
+    if let ItemState::Exists { child, .. } = state {
+        let child = child.unwrap();
+        assert!((child.p_goal - 0.75).abs() < 1e-12);
+        assert!((child.exp_cost - 7.0).abs() < 1e-12);
+        for outcome in child.outcomes() {
+            if let ItemState::Exists { purchases, .. } = outcome {
+                assert_eq!(purchases, &[1]);
+            }
+        }
+    }
+
+    // Two scrolls are already owned, so using them only costs the White
+    // Scroll. After that, the scroll is used over and over at full price.
+    let scrolls = Scroll::combinations(
+        &[Scroll::new(0.5, false, 10.0, Stats::from_vec(vec![1]))
+            .with_pricing(Pricing::owned(0, 2))],
+        &[Modifier::white_scroll(1.0)],
+    );
+    assert_eq!(scrolls[1].cost_given(&[], &[]), 1.0);
+    assert_eq!(scrolls[1].cost_given(&[2], &[]), 11.0);
+    let mut state = ItemState::new_exists(1, Stats::from_vec(vec![0]));
+    solve_p(&mut state, &scrolls, &goal, &[]);
+    if let ItemState::Exists { child, .. } = state {
+        let child = child.unwrap();
+        assert!((child.p_goal - 1.0).abs() < 1e-12);
+        assert!((child.exp_cost - 7.0).abs() < 1e-12);
+    }
+    let mut state = ItemState::new_exists(1, Stats::from_vec(vec![0]))
+        .with_purchases(vec![2]);
+    solve_p(&mut state, &scrolls, &goal, &[]);
+    if let ItemState::Exists { child, .. } = state {
+        assert!((child.unwrap().exp_cost - 22.0).abs() < 1e-12);
+    }
+}
+
+#[test]
+fn currency_test() {
+    use crate::{
+        dfs::{
+            solve, solve_cost, solve_lexicographic, solve_p, solve_p_random,
+            solve_pareto, solve_profit, solve_reward,
+        },
+        goal::Goal,
+        graph::{ItemState, ScrollUse},
+        objective::{Exchange, MaxP},
+        reward::RewardTable,
+        scroll::{Modifier, Pricing, Scroll},
+        stats::Stats,
+    };
+    use std::slice;
+
+    // Costs are in mesos and NX, respectively.
+    let goal = Goal::from(Stats::from_vec(vec![1]));
+    let init_state = ItemState::new_exists(1, Stats::from_vec(vec![0]));
+
+    // Two scrolls that are just as good, but cost different currencies. Which
+    // one is cheaper depends on the exchange rate.
+    let scrolls = [
+        Scroll::new(1.0, false, 0.0, Stats::from_vec(vec![1]))
+            .with_costs(vec![1_000.0, 0.0]),
+        Scroll::new(1.0, false, 0.0, Stats::from_vec(vec![1]))
+            .with_costs(vec![0.0, 1.0]),
+    ];
+    for (rates, choice, exp_costs) in [
+        ([1.0, 500.0], 1, [0.0, 1.0]),
+        ([1.0, 2_000.0], 0, [1_000.0, 0.0]),
+    ] {
+        let mut state = init_state.clone();
+        solve(
+            &mut state,
+            &scrolls,
+            &Exchange::new(MaxP::new(&goal), &rates),
+        );
+        if let ItemState::Exists { child, .. } = state {
+            let child = child.unwrap();
+            assert_eq!(child.scroll(), Some(&scrolls[choice]));
+            assert_eq!(child.exp_costs, exp_costs);
+        }
+    }
+
+    // The other modes go by the exchange rates, too.
+    let rates = [1.0, 2_000.0];
+    let mut state = init_state.clone();
+    let cost_per_goal = solve_cost(&mut state, &scrolls, &goal, &rates);
+    assert!((cost_per_goal - 1_000.0).abs() < 1e-9);
+    let mut state = init_state.clone();
+    let profit = solve_profit(&mut state, &scrolls, &goal, 1_500.0, &rates);
+    assert!((profit - 500.0).abs() < 1e-9);
+    let mut states = [(1.0, init_state.clone())];
+    let (_, exp_cost) = solve_p_random(&mut states, &scrolls, &goal, &rates);
+    assert!((exp_cost - 1_000.0).abs() < 1e-9);
+    let front = solve_pareto(
+        &init_state,
+        &scrolls,
+        &Exchange::new(MaxP::new(&goal), &rates),
+        0.0,
+    );
+    assert_eq!(front.last().unwrap().scroll(), Some(&scrolls[0]));
+    let mut state = init_state.clone();
+    solve_p(&mut state, &scrolls, &goal, &rates);
+    if let ItemState::Exists { child, .. } = state {
+        assert_eq!(child.unwrap().scroll(), Some(&scrolls[0]));
+    }
+    let mut state = init_state.clone();
+    solve_lexicographic(&mut state, &scrolls, slice::from_ref(&goal), &rates);
+    if let ItemState::Exists { child, .. } = state {
+        assert_eq!(child.unwrap().scroll(), Some(&scrolls[0]));
+    }
+    let rewards =
+        RewardTable::from_vec(vec![(Stats::from_vec(vec![1]), 1_500.0)]);
+    let mut state = init_state.clone();
+    let net = solve_reward(&mut state, &scrolls, &rewards, &rates);
+    assert!((net - 500.0).abs() < 1e-9);
+
+    // A plain cost is in the first currency.
+    let scrolls = [Scroll::new(0.5, false, 10.0, Stats::from_vec(vec![1]))];
+    let mut state = init_state.clone();
+    solve_p(&mut state, &scrolls, &goal, &[]);
+    if let ItemState::Exists { child, .. } = state {
+        assert_eq!(child.unwrap().exp_costs, [10.0]);
+    }
+
+    // A White Scroll bought with NX, used until the scroll succeeds. It makes
+    // no difference whether the costs are set before or after the White
+    // Scroll is added.
+    let rates = [1.0, 1_000.0];
+    let modifiers = [Modifier::white_scroll(0.0).with_costs(vec![0.0, 2.0])];
+    let scrolls = Scroll::combinations(
+        &[Scroll::new(0.5, false, 0.0, Stats::from_vec(vec![1]))
+            .with_costs(vec![100.0, 1.0])],
+        &modifiers,
+    );
+    assert_eq!(scrolls[1].costs(), [100.0, 3.0]);
+    assert_eq!(scrolls[1].cost_given(&[], &rates), 3_100.0);
+    let scroll_use = ScrollUse::new(&scrolls[1], &rates);
+    assert_eq!(
+        (scroll_use.exp_cost, scroll_use.exp_costs),
+        (3_100.0, vec![100.0, 3.0])
+    );
+    assert_eq!(
+        Scroll::new(0.5, false, 0.0, Stats::from_vec(vec![1]))
+            .with_modifier(&modifiers[0])
+            .with_costs(vec![100.0, 1.0]),
+        scrolls[1],
+    );
+    let mut state = init_state.clone();
+    solve(
+        &mut state,
+        &scrolls,
+        &Exchange::new(MaxP::new(&goal), &rates),
+    );
+    if let ItemState::Exists { child, .. } = state {
+        let child = child.unwrap();
+        assert!((child.exp_cost - 6_200.0).abs() < 1e-9);
+        assert_eq!(child.exp_costs.len(), 2);
+        assert!((child.exp_costs[0] - 200.0).abs() < 1e-9);
+        assert!((child.exp_costs[1] - 6.0).abs() < 1e-9);
+    }
+    let front = solve_pareto(
+        &init_state,
+        &scrolls,
+        &Exchange::new(MaxP::new(&goal), &rates),
+        0.0,
+    );
+    let likeliest = front.last().unwrap();
+    assert!((likeliest.p_goal - 1.0).abs() < 1e-12);
+    assert!((likeliest.exp_costs[0] - 200.0).abs() < 1e-9);
+    assert!((likeliest.exp_costs[1] - 6.0).abs() < 1e-9);
+
+    // A tier's price is in the first currency, and replaces the scroll's
+    // costs in every currency, but not the cost of the White Scroll.
+    let scroll = Scroll::new(0.5, false, 0.0, Stats::from_vec(vec![1]))
+        .with_costs(vec![100.0, 1.0])
+        .with_pricing(Pricing::new(0, vec![(1, 50.0)]))
+        .with_modifier(&modifiers[0]);
+    assert_eq!(scroll.cost_given(&[], &rates), 2_050.0);
+    assert_eq!(scroll.costs_given(&[]), [50.0, 2.0]);
+    assert_eq!(scroll.costs_given(&[1]), [100.0, 3.0]);
+}
+
+#[test]
+fn random_start_test() {
+    use crate::{
+        dfs::{solve_p_random, solve_random},
+        goal::Goal,
+        graph::ItemState,
+        objective::MaxP,
+        scroll::Scroll,
+        stats::Stats,
+    };
+    use std::rc::Rc;
+
+    let scrolls = [Scroll::new(0.6, false, 10.0, Stats::from_vec(vec![2]))];
+    let goal = Goal::from(Stats::from_vec(vec![4]));
+
+    // The first initial state can reach the second.
+    let mut states = [
+        (0.5, ItemState::new_exists(2, Stats::from_vec(vec![0]))),
+        (0.5, ItemState::new_exists(1, Stats::from_vec(vec![2]))),
+    ];
+    let (p_goal, exp_cost) = solve_p_random(&mut states, &scrolls, &goal, &[]);
+    assert!((p_goal - 0.48).abs() < 1e-12);
+    assert!((exp_cost - 13.0).abs() < 1e-12);
+
+    let children: Vec<_> = states
+        .iter()
+        .map(|(_, state)| match state {
+            ItemState::Exists { child, .. } => {
+                Rc::clone(child.as_ref().unwrap())
+            }
+            ItemState::Boomed => unreachable!(),
+        })
+        .collect();
+    assert!((children[0].p_goal - 0.36).abs() < 1e-12);
+    assert!((children[1].p_goal - 0.6).abs() < 1e-12);
+
+    // Both searches share the same cache, so they share the same tree from
+    // the second initial state onwards.
+    if let ItemState::Exists { child, .. } = &children[0].outcomes()[0] {
+        assert!(Rc::ptr_eq(child.as_ref().unwrap(), &children[1]));
+    } else {
+        unreachable!()
+    }
+
+    // The strategy for each initial state comes back alongside the
+    // expectation.
+    let strategy = solve_random(&mut states, &scrolls, &MaxP::new(&goal));
+    assert!((strategy.p_goal - 0.48).abs() < 1e-12);
+    assert_eq!(strategy.strategies.len(), 2);
+    for ((_, state), root) in states.iter().zip(strategy.strategies.iter()) {
+        if let ItemState::Exists { child, .. } = state {
+            assert!(Rc::ptr_eq(
+                child.as_ref().unwrap(),
+                root.as_ref().unwrap()
+            ));
+        }
     }
 }
@@ -0,0 +1,338 @@
+use crate::{goal::Goal, graph::ScrollUse, stats::Stats};
+
+/// Something that a search (see `dfs::solve`) can optimise for. An objective
+/// decides:
+///
+/// - How an item is valued once scrolling stops (`Objective::leaf`).
+/// - How the values of the outcomes of a scroll combine into the value of
+///   using that scroll (`Objective::accumulate`).
+/// - Which of two competing scroll uses is the better choice
+///   (`Objective::prefers`).
+/// - Which items aren't worth searching from at all
+///   (`Objective::reachable`).
+/// - What each currency is worth (`Objective::rates`).
+///
+/// Values are stored in the `p_goal`, `p_goals`, `exp_cost`, `exp_costs`, and
+/// `exp_reward` members of each `ScrollUse` in the strategy tree, so an
+/// objective can use those members however it sees fit. Every implementation
+/// in this crate uses them for their usual meanings, though.
+pub trait Objective {
+    /// Values an item with stats `stats` that will not be scrolled any
+    /// further. `stop` is a freshly created `ScrollUse` for `Action::Stop`,
+    /// with `p_goal`, `exp_cost`, and `exp_reward` all set to zero, and
+    /// `p_goals` empty; this method fills in whichever of them it needs to.
+    fn leaf(&self, stats: &Stats, stop: &mut ScrollUse<'_>);
+
+    /// Adds the contribution of one outcome of a scroll to `scroll_use`,
+    /// which is the use of that scroll. The outcome occurs with probability
+    /// `p_outcome`, and `outcome` is the choice that was made after it, or
+    /// `None` if the outcome is that the item is boomed.
+    ///
+    /// Before any outcomes are added, `scroll_use` has the expected cost of
+    /// using its scroll (see `ScrollUse`) as its `exp_cost`, and likewise for
+    /// each currency in `exp_costs`, no `p_goals`, and zero for everything
+    /// else. The default implementation takes the expectation of `p_goal`,
+    /// each of `p_goals`, `exp_cost`, each of `exp_costs`, and `exp_reward`
+    /// over all outcomes, counting a boomed item as zero for all of them.
+    fn accumulate(
+        &self,
+        scroll_use: &mut ScrollUse<'_>,
+        p_outcome: f64,
+        outcome: Option<&ScrollUse<'_>>,
+    ) {
+        if let Some(outcome) = outcome {
+            if scroll_use.p_goals.len() < outcome.p_goals.len() {
+                scroll_use.p_goals.resize(outcome.p_goals.len(), 0.0);
+            }
+            for (p_goal, outcome_p_goal) in
+                scroll_use.p_goals.iter_mut().zip(outcome.p_goals.iter())
+            {
+                *p_goal += p_outcome * outcome_p_goal;
+            }
+
+            if scroll_use.exp_costs.len() < outcome.exp_costs.len() {
+                scroll_use.exp_costs.resize(outcome.exp_costs.len(), 0.0);
+            }
+            for (exp_cost, outcome_exp_cost) in scroll_use
+                .exp_costs
+                .iter_mut()
+                .zip(outcome.exp_costs.iter())
+            {
+                *exp_cost += p_outcome * outcome_exp_cost;
+            }
+
+            scroll_use.p_goal += p_outcome * outcome.p_goal;
+            scroll_use.exp_cost += p_outcome * outcome.exp_cost;
+            scroll_use.exp_reward += p_outcome * outcome.exp_reward;
+        }
+    }
+
+    /// Returns `true` iff `scroll_use` is a strictly better choice than
+    /// `incumbent`.
+    fn prefers(
+        &self,
+        scroll_use: &ScrollUse<'_>,
+        incumbent: &ScrollUse<'_>,
+    ) -> bool;
+
+    /// Returns `false` only if no item whose stats lie between `lo` and `hi`
+    /// (inclusive, in every stat) is worth anything, i.e. `Objective::leaf`
+    /// would leave it with zero `p_goal`, `p_goals`, and `exp_reward`. The
+    /// search uses this, along with the "master scroll" heuristic, to avoid
+    /// exploring outcomes from which nothing can be gained; such outcomes are
+    /// left out of their `ScrollUse` entirely. The default implementation
+    /// always returns `true`, which is always correct, but prunes nothing.
+    fn reachable(&self, _lo: &Stats, _hi: &Stats) -> bool {
+        true
+    }
+
+    /// Returns the exchange rates between currencies, i.e. what one of each
+    /// currency (see `Scroll::costs`) is worth, in the units of `exp_cost`.
+    /// Wherever the search needs the cost of a scroll as one number, e.g. for
+    /// `exp_cost`, or to keep within a budget, it totals the costs in every
+    /// currency at these rates. Currencies past the end of the rates are
+    /// worth one apiece, and so the default implementation, which has no
+    /// rates at all, counts every currency the same as currency zero.
+    fn rates(&self) -> &[f64] {
+        &[]
+    }
+}
+
+/// A borrowed objective is the same objective, e.g. so that it can be wrapped
+/// in an `Exchange` without giving it up.
+impl<O: Objective + ?Sized> Objective for &O {
+    fn leaf(&self, stats: &Stats, stop: &mut ScrollUse<'_>) {
+        (**self).leaf(stats, stop);
+    }
+
+    fn accumulate(
+        &self,
+        scroll_use: &mut ScrollUse<'_>,
+        p_outcome: f64,
+        outcome: Option<&ScrollUse<'_>>,
+    ) {
+        (**self).accumulate(scroll_use, p_outcome, outcome);
+    }
+
+    fn prefers(
+        &self,
+        scroll_use: &ScrollUse<'_>,
+        incumbent: &ScrollUse<'_>,
+    ) -> bool {
+        (**self).prefers(scroll_use, incumbent)
+    }
+
+    fn reachable(&self, lo: &Stats, hi: &Stats) -> bool {
+        (**self).reachable(lo, hi)
+    }
+
+    fn rates(&self) -> &[f64] {
+        (**self).rates()
+    }
+}
+
+/// Optimise for `objective`, except that costs in different currencies are
+/// totalled at `rates` apiece (see `Objective::rates`).
+#[derive(Clone, Copy, Debug)]
+pub struct Exchange<'r, O> {
+    objective: O,
+    rates: &'r [f64],
+}
+
+impl<'r, O> Exchange<'r, O> {
+    /// Creates a new instance of this objective, for optimising for
+    /// `objective` at the exchange rates `rates`.
+    pub const fn new(objective: O, rates: &'r [f64]) -> Self {
+        Self { objective, rates }
+    }
+}
+
+impl<O: Objective> Objective for Exchange<'_, O> {
+    fn leaf(&self, stats: &Stats, stop: &mut ScrollUse<'_>) {
+        self.objective.leaf(stats, stop);
+    }
+
+    fn accumulate(
+        &self,
+        scroll_use: &mut ScrollUse<'_>,
+        p_outcome: f64,
+        outcome: Option<&ScrollUse<'_>>,
+    ) {
+        self.objective.accumulate(scroll_use, p_outcome, outcome);
+    }
+
+    fn prefers(
+        &self,
+        scroll_use: &ScrollUse<'_>,
+        incumbent: &ScrollUse<'_>,
+    ) -> bool {
+        self.objective.prefers(scroll_use, incumbent)
+    }
+
+    fn reachable(&self, lo: &Stats, hi: &Stats) -> bool {
+        self.objective.reachable(lo, hi)
+    }
+
+    fn rates(&self) -> &[f64] {
+        self.rates
+    }
+}
+
+/// Maximise the probability of reaching `goal`, going with lower expected
+/// costs only when needed to break a tie. This is the objective of
+/// `dfs::solve_p`.
+#[derive(Clone, Copy, Debug)]
+pub struct MaxP<'g> {
+    goal: &'g Goal,
+}
+
+impl<'g> MaxP<'g> {
+    /// Creates a new instance of this objective, for reaching `goal`.
+    pub const fn new(goal: &'g Goal) -> Self {
+        Self { goal }
+    }
+}
+
+impl Objective for MaxP<'_> {
+    fn leaf(&self, stats: &Stats, stop: &mut ScrollUse<'_>) {
+        stop.p_goal = if self.goal.is_met(stats) { 1.0 } else { 0.0 };
+    }
+
+    fn prefers(
+        &self,
+        scroll_use: &ScrollUse<'_>,
+        incumbent: &ScrollUse<'_>,
+    ) -> bool {
+        scroll_use.p_goal > incumbent.p_goal
+            || (scroll_use.p_goal >= incumbent.p_goal
+                && scroll_use.exp_cost < incumbent.exp_cost)
+    }
+
+    fn reachable(&self, lo: &Stats, hi: &Stats) -> bool {
+        self.goal.may_be_met(lo, hi)
+    }
+}
+
+/// Maximise the expected profit, i.e. `value * p_goal - exp_cost`, where
+/// `value` is how much an item that meets `goal` is worth (in the same units
+/// as scroll costs). Ties are broken by going with higher probabilities of
+/// reaching `goal`. The expected value of the item, `value * p_goal`, is
+/// stored as `exp_reward`. This is the objective of `dfs::solve_profit`.
+#[derive(Clone, Copy, Debug)]
+pub struct MaxProfit<'g> {
+    goal: &'g Goal,
+    value: f64,
+}
+
+impl<'g> MaxProfit<'g> {
+    /// Creates a new instance of this objective, for reaching `goal`, which
+    /// is worth `value`.
+    pub const fn new(goal: &'g Goal, value: f64) -> Self {
+        Self { goal, value }
+    }
+}
+
+impl Objective for MaxProfit<'_> {
+    fn leaf(&self, stats: &Stats, stop: &mut ScrollUse<'_>) {
+        if self.goal.is_met(stats) {
+            stop.p_goal = 1.0;
+            stop.exp_reward = self.value;
+        }
+    }
+
+    fn prefers(
+        &self,
+        scroll_use: &ScrollUse<'_>,
+        incumbent: &ScrollUse<'_>,
+    ) -> bool {
+        prefers_net(scroll_use, incumbent)
+    }
+
+    fn reachable(&self, lo: &Stats, hi: &Stats) -> bool {
+        self.goal.may_be_met(lo, hi)
+    }
+}
+
+/// Maximise the probability of reaching the first of `goals`, then, among the
+/// strategies that are tied for that, maximise the probability of reaching
+/// the second of `goals`, and so on, going with lower expected costs only when
+/// needed to break a tie between all of `goals`. Typically, each goal is a
+/// fallback for the one before it, e.g. ≥110 WATK, then ≥108 WATK, then ≥105
+/// WATK.
+///
+/// The probability of reaching each goal is stored in `p_goals`, and that of
+/// the first goal is also stored as `p_goal`. Probabilities that differ only
+/// negligibly are considered to be tied, so that rounding errors don't keep
+/// the later goals from breaking ties. This is the objective of
+/// `dfs::solve_lexicographic`.
+///
+/// ## Invariants:
+///
+/// - `!goals.is_empty()`
+#[derive(Clone, Copy, Debug)]
+pub struct Lexicographic<'g> {
+    goals: &'g [Goal],
+}
+
+impl<'g> Lexicographic<'g> {
+    /// Creates a new instance of this objective, for reaching `goals`, in
+    /// order of decreasing priority.
+    pub fn new(goals: &'g [Goal]) -> Self {
+        debug_assert!(!goals.is_empty());
+
+        Self { goals }
+    }
+}
+
+impl Objective for Lexicographic<'_> {
+    fn leaf(&self, stats: &Stats, stop: &mut ScrollUse<'_>) {
+        stop.p_goals = self
+            .goals
+            .iter()
+            .map(|goal| if goal.is_met(stats) { 1.0 } else { 0.0 })
+            .collect();
+        stop.p_goal = stop.p_goals[0];
+    }
+
+    fn prefers(
+        &self,
+        scroll_use: &ScrollUse<'_>,
+        incumbent: &ScrollUse<'_>,
+    ) -> bool {
+        for i in 0..self.goals.len() {
+            let p_goal = scroll_use.p_goals.get(i).copied().unwrap_or(0.0);
+            let incumbent_p_goal =
+                incumbent.p_goals.get(i).copied().unwrap_or(0.0);
+
+            if p_goal > incumbent_p_goal + LEXICOGRAPHIC_TOLERANCE {
+                return true;
+            } else if p_goal < incumbent_p_goal - LEXICOGRAPHIC_TOLERANCE {
+                return false;
+            }
+        }
+
+        scroll_use.exp_cost < incumbent.exp_cost
+    }
+
+    fn reachable(&self, lo: &Stats, hi: &Stats) -> bool {
+        self.goals.iter().any(|goal| goal.may_be_met(lo, hi))
+    }
+}
+
+/// The largest difference between two probabilities of reaching a goal that
+/// `Lexicographic` considers to be a tie.
+const LEXICOGRAPHIC_TOLERANCE: f64 = 1e-12;
+
+/// Returns `true` iff `scroll_use` has a strictly greater `exp_reward -
+/// exp_cost` than `incumbent`, going with higher `p_goal` only when needed to
+/// break a tie. This is how `MaxProfit` and `RewardTable` compare scroll uses.
+pub(crate) fn prefers_net(
+    scroll_use: &ScrollUse<'_>,
+    incumbent: &ScrollUse<'_>,
+) -> bool {
+    let net = scroll_use.exp_reward - scroll_use.exp_cost;
+    let incumbent_net = incumbent.exp_reward - incumbent.exp_cost;
+
+    net > incumbent_net
+        || (net >= incumbent_net && scroll_use.p_goal > incumbent.p_goal)
+}
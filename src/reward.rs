@@ -0,0 +1,75 @@
+use crate::{
+    graph::ScrollUse,
+    objective::{prefers_net, Objective},
+    stats::Stats,
+};
+
+/// A table of rewards for the final stats of an item, i.e. the stats that the
+/// item has once scrolling stops. Each entry of the table (a "tier") pairs a
+/// threshold with a reward. An item earns a tier's reward if its stats meet or
+/// exceed that tier's threshold, in the same way that an item meets a goal
+/// (i.e. _every_ stat must be met or exceeded). If an item meets more than one
+/// tier, it earns the largest of their rewards, and if it meets none, it earns
+/// nothing.
+///
+/// Rewards are expressed in the same units as the total cost of a scroll (see
+/// `Objective::rates`), and must be nonnegative.
+///
+/// As an `Objective`, a reward table maximises the expected reward minus the
+/// expected cost, going with higher probabilities of earning any (positive)
+/// reward at all only when needed to break a tie. This is the objective of
+/// `dfs::solve_reward`.
+///
+/// ## Invariants:
+///
+/// - Every threshold has a stat array of the same length as the stat arrays
+///   of the items and scrolls that it's used with.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RewardTable {
+    tiers: Vec<(Stats, f64)>,
+}
+
+impl RewardTable {
+    /// Creates a new reward table from a list of `(threshold, reward)` pairs.
+    /// The order of the pairs is irrelevant.
+    pub const fn from_vec(tiers: Vec<(Stats, f64)>) -> Self {
+        Self { tiers }
+    }
+
+    /// Returns a reference to the `(threshold, reward)` pairs of this table.
+    pub fn tiers(&self) -> &[(Stats, f64)] {
+        &self.tiers
+    }
+
+    /// The reward earned by an item whose final stats are `stats`.
+    pub fn reward(&self, stats: &Stats) -> f64 {
+        self.tiers
+            .iter()
+            .filter(|(threshold, _)| stats >= threshold)
+            .map(|(_, reward)| *reward)
+            .fold(0.0, f64::max)
+    }
+}
+
+impl Objective for RewardTable {
+    fn leaf(&self, stats: &Stats, stop: &mut ScrollUse<'_>) {
+        let reward = self.reward(stats);
+
+        stop.p_goal = if reward > 0.0 { 1.0 } else { 0.0 };
+        stop.exp_reward = reward;
+    }
+
+    fn prefers(
+        &self,
+        scroll_use: &ScrollUse<'_>,
+        incumbent: &ScrollUse<'_>,
+    ) -> bool {
+        prefers_net(scroll_use, incumbent)
+    }
+
+    fn reachable(&self, _lo: &Stats, hi: &Stats) -> bool {
+        self.tiers
+            .iter()
+            .any(|(threshold, reward)| *reward > 0.0 && hi >= threshold)
+    }
+}
@@ -1,4 +1,5 @@
-use crate::stats::Stats;
+use crate::stats::{StatDelta, Stats};
+use std::{fmt, iter, rc::Rc};
 
 /// A scroll. Contains all of the usual information associated with a scroll,
 /// in addition to its nominal cost.
@@ -6,62 +7,982 @@ use crate::stats::Stats;
 pub struct Scroll {
     /// Probability of success.
     pub p_suc: f64,
-    /// Is this a dark scroll?
-    pub dark: bool,
-    /// How much the scroll costs.
+    /// Probability of the scroll booming the item. Whatever is left of the
+    /// probability of failure (see `Scroll::p_miss`) is that of a miss, which
+    /// does nothing to the item other than use up one of its slots. For dark
+    /// scrolls, this is usually half of the probability of failure, and for
+    /// other scrolls, it's usually zero.
+    pub p_boom: f64,
+    /// If the probability of success depends on the state of the item that
+    /// the scroll is used on (e.g. it goes up after each miss in a row), then
+    /// this is how. `p_suc` and `p_boom` are then just the usual
+    /// probabilities; see `Scroll::probabilities` for the actual ones.
+    pub success_rate: Option<SuccessRate>,
+    /// How much the scroll costs, in currency zero (e.g. mesos). This is what
+    /// the search minimises, along with `other_costs`, if any.
     pub cost: f64,
-    /// What stats the scroll grants on success.
-    pub stats: Stats,
+    /// How much the scroll costs in each currency other than currency zero
+    /// (e.g. NX, and hours spent farming): `other_costs[i]` is the cost in
+    /// currency `i + 1`. Like stats, currencies are only told apart by their
+    /// position, so it's up to the caller to keep track of which is which.
+    ///
+    /// This is empty unless set by `Scroll::with_costs`. The search minimises
+    /// the total of every currency at the exchange rates of its objective (see
+    /// `Objective::rates`), but reports each currency separately (see
+    /// `ScrollUse::exp_costs`). Like `cost`, it includes any modifiers.
+    pub other_costs: Vec<f64>,
+    /// What the scroll does to an item on success. This is a probability
+    /// distribution: each element pairs the probability of that success
+    /// happening (given that the scroll succeeds) with what it does, and the
+    /// probabilities sum to one. Most scrolls always grant the same stats, and
+    /// so have just the one element, but e.g. a Chaos Scroll has many, some of
+    /// which decrease stats.
+    pub success: Vec<(f64, Success)>,
+    /// How a miss of this scroll changes the item's slots. This is usually
+    /// -1, as the scroll uses up the slot that it was used on, but some
+    /// scrolls use up more than one slot. Any slots that are used up are lost
+    /// to failure (see `ItemState`).
+    pub miss_slots: i8,
+    /// Does a failure of this scroll leave the item's slots as they were?
+    /// This is the case when the scroll is used alongside a White Scroll.
+    pub keeps_slot: bool,
+    /// What the scroll does to an item on success. Only
+    /// `ScrollEffect::Regular` scrolls use up slots, and change the item's
+    /// stats as given by `success`; for the other effects, `success` is
+    /// empty. See also `Scroll::kind`.
+    pub effect: ScrollEffect,
+    /// If there are only so many of this scroll to use, then the index into
+    /// the item's inventory (see `ItemState::with_inventory`) of how many are
+    /// left. Each use of the scroll takes one away, and once there are none
+    /// left (or the inventory has no such index), the scroll can't be used.
+    /// Scrolls that share an index share their inventory, which is the case
+    /// for the same scroll used alongside different modifiers (see
+    /// `Scroll::combinations`).
+    pub inventory: Option<usize>,
+    /// If the price of the scroll depends on how many of it have already been
+    /// bought (e.g. the market sells the first few cheaply), then how. `cost`
+    /// and `other_costs` are then the usual costs, i.e. the costs once every
+    /// tier of the pricing has been bought up.
+    pub pricing: Option<Pricing>,
+    /// The modifiers (see `Modifier`) that are used alongside this scroll.
+    /// These are already accounted for by the other members, so this is just
+    /// a record of where they came from.
+    pub modifiers: Vec<Modifier>,
+}
+
+/// One of the ways in which a scroll can succeed. See `Scroll::success`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Success {
+    /// How the scroll changes the item's stats.
+    pub stats: StatDelta,
+    /// How the scroll changes the item's slots. This is usually -1, as the
+    /// scroll uses up the slot that it was used on, but some scrolls use up
+    /// more than one slot, and some even grant slots. Beware that if a
+    /// success doesn't use up any slots, then there's no limit to how many
+    /// scrolls could be used on an item other than the budget, so searching
+    /// with such a scroll panics unless the item has a (finite) budget, and
+    /// can take a very long time if the budget is large compared to the
+    /// scroll's cost.
+    pub slots: i8,
+}
+
+/// The probability of success of a scroll, as a function of the state of the
+/// item that it's used on. See `Scroll::success_rate`.
+#[derive(Clone)]
+pub struct SuccessRate(Rc<RateFn>);
+
+/// The function behind a `SuccessRate`. See `SuccessRate::new`.
+type RateFn = dyn Fn(f64, &ItemContext<'_>) -> f64;
+
+/// The state of an item that a scroll is about to be used on, as far as a
+/// `SuccessRate` is concerned. See `ItemState`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ItemContext<'s> {
+    /// The slots that the item has left.
+    pub slots: u8,
+    /// The slots that the item has lost to failed scrolls.
+    pub failed_slots: u8,
+    /// How many more times a hammer can be used on the item.
+    pub hammers: u8,
+    /// How many scrolls in a row have missed since the item last had a
+    /// scroll succeed on it. This is only kept track of when some scroll has
+    /// a `Scroll::success_rate` (see `MasterScroll::streaks`); otherwise,
+    /// it's always zero.
+    pub streak: u8,
+    /// How many scrolls have succeeded on the item, not counting Clean Slate
+    /// Scrolls and hammers, since it was last reset (see
+    /// `ScrollEffect::Reset`). Like `streak`, this is only kept track of when
+    /// some scroll has a `Scroll::success_rate`; otherwise, it stays as it was
+    /// in the initial item state (see `ItemState::with_upgrades`).
+    pub upgrades: u8,
+    /// The item's stats.
+    pub stats: &'s Stats,
+}
+
+/// How the price of a scroll depends on how many of it have already been
+/// bought. See `Scroll::pricing`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pricing {
+    /// The index into the item's purchases (see `ItemState::with_purchases`)
+    /// of how many of the scroll have been bought. Scrolls that share an index
+    /// are bought from the same tiers, which is the case for the same scroll
+    /// used alongside different modifiers (see `Scroll::combinations`).
+    pub purchases: usize,
+    /// The first `tiers[0].0` scrolls that are bought cost `tiers[0].1` each,
+    /// the next `tiers[1].0` cost `tiers[1].1` each, and so on. Any that are
+    /// bought after that cost the usual `Scroll::costs`. These prices are in
+    /// currency zero, and are for the scroll alone, in place of what it
+    /// usually costs in every currency; any modifiers that it's used alongside
+    /// cost extra, as usual.
+    pub tiers: Vec<(u8, f64)>,
+}
+
+/// Something that is used alongside a scroll to change how that scroll
+/// behaves, like a White Scroll, a Protect Shield, or a Lucky Day scroll.
+/// Each modifier is used up, and so costs its `cost`, whenever a scroll is
+/// used alongside it, regardless of how the scroll turns out. See
+/// `Scroll::with_modifier`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Modifier {
+    /// How much the modifier costs, in currency zero. See `Scroll::cost`.
+    pub cost: f64,
+    /// How much the modifier costs in each currency other than currency zero.
+    /// See `Scroll::other_costs`.
+    pub other_costs: Vec<f64>,
+    /// How much the modifier adds to the probability of success of the
+    /// scroll. The resulting probability is capped at 100%.
+    pub p_suc_bonus: f64,
+    /// Does a failure of the scroll leave the item's slots as they were?
+    pub keeps_slot: bool,
+    /// Is a failure of the scroll prevented from booming the item?
+    pub protects: bool,
+}
+
+/// A server-wide event that changes the probability of success of scrolls,
+/// e.g. "+10% to all success rates". See `Event::apply`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Event {
+    /// What the probability of success of each affected scroll is multiplied
+    /// by.
+    pub p_suc_factor: f64,
+    /// How much is then added to the probability of success of each affected
+    /// scroll. The resulting probability is capped at 100%.
+    pub p_suc_bonus: f64,
+    /// The kinds of scroll that the event affects.
+    pub kinds: Vec<ScrollKind>,
+    /// If set, then the event only affects those scrolls of `kinds` for which
+    /// this returns `true`, e.g. only dark scrolls, or only 10% scrolls. See
+    /// `Event::with_filter`.
+    pub filter: Option<ScrollFilter>,
+}
+
+/// Which scrolls an event affects, beyond their kinds. See `Event::filter`.
+#[derive(Clone)]
+pub struct ScrollFilter(Rc<FilterFn>);
+
+/// The function behind a `ScrollFilter`. See `ScrollFilter::new`.
+type FilterFn = dyn Fn(&Scroll) -> bool;
+
+/// What a scroll does to an item on success. See `Scroll::effect`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ScrollEffect {
+    /// A scroll that uses up slots to change the item's stats.
+    Regular,
+    /// A Clean Slate Scroll. Rather than using up one of the item's slots, a
+    /// Clean Slate Scroll can only be used on an item that has lost slots to
+    /// failed scrolls, and on success, it recovers one of them. Either way,
+    /// it doesn't change the item's stats.
+    CleanSlate,
+    /// A hammer (e.g. Vicious' Hammer). Rather than using up one of the
+    /// item's slots, a hammer can only be used on an item that has hammers
+    /// left (see `ItemState::with_hammers`), and on success, it adds a slot
+    /// to the item, using up one of the hammers. Either way, it doesn't
+    /// change the item's stats.
+    Hammer,
+    /// A reset scroll (e.g. an Innocence Scroll). Rather than using up one of
+    /// the item's slots, a reset scroll can be used on any item, and on
+    /// success, it restores the item to its clean slots and stats, with no
+    /// slots lost to failed scrolls. It doesn't give back any hammers that
+    /// have been used, though.
+    Reset {
+        /// The slots of the clean item.
+        slots: u8,
+        /// The stats of the clean item.
+        stats: Stats,
+    },
+}
+
+/// What kind of scroll a scroll is, regardless of the details of what it
+/// does. See `Scroll::kind`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ScrollKind {
+    /// See `ScrollEffect::Regular`.
+    Regular,
+    /// See `ScrollEffect::CleanSlate`.
+    CleanSlate,
+    /// See `ScrollEffect::Hammer`.
+    Hammer,
+    /// A reset scroll, whatever it resets the item to. See
+    /// `ScrollEffect::Reset`.
+    Reset,
+}
+
+/// The "master scroll" for a set of scrolls, which bounds what using any of
+/// those scrolls can do to the stats of an item. See `Scroll::master_scroll`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MasterScroll {
+    /// The greatest increase to each stat that a single use of any of the
+    /// scrolls can confer. This is never negative.
+    pub best: StatDelta,
+    /// The greatest decrease to each stat that a single use of any of the
+    /// scrolls can confer. This is never positive.
+    pub worst: StatDelta,
+    /// Can any of the scrolls recover failed slots? See
+    /// `ScrollEffect::CleanSlate`.
+    pub clean_slate: bool,
+    /// Can any of the scrolls add slots? See `ScrollEffect::Hammer`.
+    pub hammer: bool,
+    /// The clean slots and stats that any of the scrolls can reset an item
+    /// to. See `ScrollEffect::Reset`.
+    pub resets: Vec<(u8, Stats)>,
+    /// Can any of the scrolls change the item's stats without using up a
+    /// slot, or add slots to the item (other than as described by
+    /// `MasterScroll::clean_slate` and `MasterScroll::hammer`)? If so, then
+    /// there's no telling how many more scrolls could be used on an item.
+    pub grants_slots: bool,
+    /// Does the probability of success of any of the scrolls depend on the
+    /// state of the item? See `Scroll::success_rate`. If so, then the search
+    /// keeps track of how many scrolls in a row have missed, of how many
+    /// upgrades the item has had, and of the slots lost to failed scrolls (see
+    /// `ItemContext`).
+    pub streaks: bool,
 }
 
 impl Scroll {
     /// Creates a new scroll from the probability of success (`p_suc`), whether
     /// or not the scroll is dark (`dark`), what the nominal cost of the scroll
-    /// is (`cost`), and what stats the scroll grants on success (`stats`).
-    pub const fn new(p_suc: f64, dark: bool, cost: f64, stats: Stats) -> Self {
+    /// is (`cost`), and how the scroll changes stats on success (`stats`).
+    /// `stats` is usually just the `Stats` that the scroll grants, but it can
+    /// also be a `StatDelta`, for scrolls that decrease some stats.
+    pub fn new(
+        p_suc: f64,
+        dark: bool,
+        cost: f64,
+        stats: impl Into<StatDelta>,
+    ) -> Self {
+        Self::new_chaos(p_suc, dark, cost, vec![(1.0, stats.into())])
+    }
+
+    /// Creates a new scroll, like `Scroll::new`, except that what the scroll
+    /// does to an item's stats on success is a probability distribution over
+    /// changes to stats (`success`). See the documentation for
+    /// `Scroll::success`.
+    pub fn new_chaos(
+        p_suc: f64,
+        dark: bool,
+        cost: f64,
+        success: Vec<(f64, StatDelta)>,
+    ) -> Self {
+        Self::new_with_p_boom(
+            p_suc,
+            p_boom_if_dark(p_suc, dark),
+            cost,
+            success
+                .into_iter()
+                .map(|(p, delta)| (p, Success::from(delta)))
+                .collect(),
+        )
+    }
+
+    /// Creates a new scroll, like `Scroll::new_chaos`, except that rather
+    /// than the scroll being dark or not, the probability of it booming the
+    /// item (`p_boom`) is given explicitly, and each success can change the
+    /// item's slots however it likes. See the documentation for
+    /// `Scroll::p_boom` and for `Scroll::success`. A miss uses up one slot.
+    ///
+    /// ## Invariants:
+    ///
+    /// - `p_suc + p_boom <= 1.0`
+    pub const fn new_with_p_boom(
+        p_suc: f64,
+        p_boom: f64,
+        cost: f64,
+        success: Vec<(f64, Success)>,
+    ) -> Self {
         Self {
             p_suc,
-            dark,
+            p_boom,
+            success_rate: None,
             cost,
-            stats,
+            other_costs: Vec::new(),
+            success,
+            miss_slots: -1,
+            keeps_slot: false,
+            effect: ScrollEffect::Regular,
+            inventory: None,
+            pricing: None,
+            modifiers: Vec::new(),
+        }
+    }
+
+    /// Creates a new Clean Slate Scroll (see `ScrollEffect::CleanSlate`)
+    /// from the probability of success (`p_suc`), whether or not the scroll
+    /// is dark (`dark`), and what the nominal cost of the scroll is (`cost`).
+    pub fn new_clean_slate(p_suc: f64, dark: bool, cost: f64) -> Self {
+        Self {
+            miss_slots: 0,
+            effect: ScrollEffect::CleanSlate,
+            ..Self::new_with_p_boom(
+                p_suc,
+                p_boom_if_dark(p_suc, dark),
+                cost,
+                Vec::new(),
+            )
         }
     }
 
+    /// Creates a new hammer (see `ScrollEffect::Hammer`) from the probability
+    /// of success (`p_suc`), and what the nominal cost of using the hammer is
+    /// (`cost`).
+    pub fn new_hammer(p_suc: f64, cost: f64) -> Self {
+        Self {
+            miss_slots: 0,
+            effect: ScrollEffect::Hammer,
+            ..Self::new_with_p_boom(p_suc, 0.0, cost, Vec::new())
+        }
+    }
+
+    /// Creates a new reset scroll (see `ScrollEffect::Reset`) from the
+    /// probability of success (`p_suc`), whether or not the scroll is dark
+    /// (`dark`), what the nominal cost of the scroll is (`cost`), and the
+    /// slots and stats of the clean item (`slots` and `stats`, respectively).
+    pub fn new_reset(
+        p_suc: f64,
+        dark: bool,
+        cost: f64,
+        slots: u8,
+        stats: Stats,
+    ) -> Self {
+        Self {
+            miss_slots: 0,
+            effect: ScrollEffect::Reset { slots, stats },
+            ..Self::new_with_p_boom(
+                p_suc,
+                p_boom_if_dark(p_suc, dark),
+                cost,
+                Vec::new(),
+            )
+        }
+    }
+
+    /// Returns this scroll, except that its probability of success depends on
+    /// the state of the item that it's used on, as given by `success_rate`.
+    pub fn with_success_rate(mut self, success_rate: SuccessRate) -> Self {
+        self.success_rate = Some(success_rate);
+
+        self
+    }
+
+    /// Returns this scroll, except that there are only so many of it to use,
+    /// as given by index `inventory` into the item's inventory. See
+    /// `Scroll::inventory`.
+    pub fn with_inventory(mut self, inventory: usize) -> Self {
+        self.inventory = Some(inventory);
+
+        self
+    }
+
+    /// Returns this scroll, except that its price depends on how many of it
+    /// have already been bought, as given by `pricing`.
+    pub fn with_pricing(mut self, pricing: Pricing) -> Self {
+        self.pricing = Some(pricing);
+
+        self
+    }
+
+    /// Returns this scroll, except that, on its own, it costs `costs[i]` in
+    /// currency `i` (see `Scroll::other_costs`). Any modifiers that it's
+    /// already used alongside still cost extra.
+    pub fn with_costs(mut self, mut costs: Vec<f64>) -> Self {
+        for modifier in self.modifiers.iter() {
+            add_costs(&mut costs, &modifier.costs());
+        }
+        let (cost, other_costs) = split_costs(costs);
+        self.cost = cost;
+        self.other_costs = other_costs;
+
+        self
+    }
+
+    /// Returns how much the scroll costs in each currency, starting with
+    /// currency zero, i.e. `cost` followed by `other_costs`.
+    pub fn costs(&self) -> Vec<f64> {
+        iter::once(self.cost)
+            .chain(self.other_costs.iter().copied())
+            .collect()
+    }
+
+    /// Returns the cost of using this scroll on an item for which `purchases`
+    /// (see `ItemState::with_purchases`) have been bought, in total, at
+    /// `rates` apiece (see `Objective::rates`).
+    pub fn cost_given(&self, purchases: &[u8], rates: &[f64]) -> f64 {
+        exchange(&self.costs_given(purchases), rates)
+    }
+
+    /// Like `Scroll::cost_given`, but for the cost in each currency (see
+    /// `Scroll::costs`). This is `Scroll::costs`, unless the scroll has a
+    /// `Scroll::pricing`, and not every tier of it has been bought up.
+    pub fn costs_given(&self, purchases: &[u8]) -> Vec<f64> {
+        let price = if let Some(price) = self.tier_price(purchases) {
+            price
+        } else {
+            return self.costs();
+        };
+
+        let mut costs = vec![price];
+        for modifier in self.modifiers.iter() {
+            add_costs(&mut costs, &modifier.costs());
+        }
+
+        costs
+    }
+
+    /// Returns the price of this scroll alone (without any modifiers) if
+    /// `purchases` have been bought, and it's priced by a tier of its
+    /// `Scroll::pricing`.
+    fn tier_price(&self, purchases: &[u8]) -> Option<f64> {
+        let pricing = self.pricing.as_ref()?;
+
+        let mut bought = purchases
+            .get(pricing.purchases)
+            .copied()
+            .unwrap_or_default();
+        for &(count, price) in pricing.tiers.iter() {
+            if bought < count {
+                return Some(price);
+            }
+            bought -= count;
+        }
+
+        None
+    }
+
+    /// Returns the probabilities of success, of booming the item, and of a
+    /// miss (respectively) when this scroll is used on the item `item`. These
+    /// are `p_suc`, `p_boom`, and `Scroll::p_miss`, unless the scroll has a
+    /// `Scroll::success_rate`, in which case any change to the probability of
+    /// success comes out of (or goes into) the probabilities of missing and of
+    /// booming in proportion to their size.
+    pub fn probabilities(&self, item: &ItemContext<'_>) -> (f64, f64, f64) {
+        match &self.success_rate {
+            Some(success_rate) => {
+                let p_suc = success_rate.p_suc(self.p_suc, item);
+                let p_boom = scale_p_boom(self.p_boom, self.p_suc, p_suc);
+
+                (p_suc, p_boom, (1.0 - p_suc - p_boom).max(0.0))
+            }
+            None => (self.p_suc, self.p_boom, self.p_miss()),
+        }
+    }
+
+    /// Returns the probability of a miss, i.e. of the scroll failing without
+    /// booming the item.
+    pub fn p_miss(&self) -> f64 {
+        (1.0 - self.p_suc - self.p_boom).max(0.0)
+    }
+
+    /// Returns the number of slots that an item must have for this scroll to
+    /// be used on it, i.e. the most slots that the scroll can use up, or one
+    /// slot, whichever is more. Scrolls other than `ScrollKind::Regular`
+    /// ones don't use up slots, and so need none.
+    pub fn slots_needed(&self) -> u8 {
+        if self.kind() != ScrollKind::Regular {
+            return 0;
+        }
+
+        self.success
+            .iter()
+            .map(|(_, success)| success.slots)
+            .chain(iter::once(self.miss_slots))
+            .map(|slots| slots.min(0).unsigned_abs())
+            .max()
+            .unwrap_or_default()
+            .max(1)
+    }
+
+    /// Returns what kind of scroll this is.
+    pub const fn kind(&self) -> ScrollKind {
+        match self.effect {
+            ScrollEffect::Regular => ScrollKind::Regular,
+            ScrollEffect::CleanSlate => ScrollKind::CleanSlate,
+            ScrollEffect::Hammer => ScrollKind::Hammer,
+            ScrollEffect::Reset { .. } => ScrollKind::Reset,
+        }
+    }
+
+    /// Returns `true` iff the scroll can boom the item.
+    pub fn is_dark(&self) -> bool {
+        self.p_boom > 0.0
+    }
+
+    /// Returns the scroll that results from using `self` alongside
+    /// `modifier`. The resulting scroll costs as much as both put together.
+    /// Any increase to the probability of success comes out of the
+    /// probabilities of missing and of booming in proportion to their size.
+    pub fn with_modifier(&self, modifier: &Modifier) -> Self {
+        let mut scroll = self.clone();
+        if modifier.protects {
+            scroll.p_boom = 0.0;
+        }
+        let p_suc = (scroll.p_suc + modifier.p_suc_bonus).min(1.0);
+        scroll.p_boom = scale_p_boom(scroll.p_boom, scroll.p_suc, p_suc);
+        scroll.p_suc = p_suc;
+        scroll.cost += modifier.cost;
+        add_costs(&mut scroll.other_costs, &modifier.other_costs);
+        scroll.keeps_slot |= modifier.keeps_slot;
+        scroll.modifiers.push(modifier.clone());
+
+        scroll
+    }
+
+    /// Returns every way of using one of `scrolls` alongside any number of
+    /// `modifiers` (including none at all), with each modifier being used at
+    /// most once. Passing the result to a search function lets it choose
+    /// which modifiers, if any, are worth using.
+    ///
+    /// Note that there are `scrolls.len() * 2.pow(modifiers.len())` such
+    /// ways.
+    pub fn combinations(
+        scrolls: &[Self],
+        modifiers: &[Modifier],
+    ) -> Vec<Self> {
+        scrolls
+            .iter()
+            .flat_map(|scroll| {
+                (0..1usize << modifiers.len()).map(move |subset| {
+                    modifiers
+                        .iter()
+                        .enumerate()
+                        .filter(|(i, _)| subset & (1 << i) != 0)
+                        .fold(scroll.clone(), |scroll, (_, modifier)| {
+                            scroll.with_modifier(modifier)
+                        })
+                })
+            })
+            .collect()
+    }
+
     /// Generates a "master scroll" based on a set of `Scroll`s. The master
-    /// scroll has a 100% probability of success, is not a dark scroll, has a
-    /// cost equal to [positive
-    /// infinity](https://en.wikipedia.org/wiki/Extended_real_number_line), and
-    /// grants a bonus to each stat that is equal to the highest bonus granted
-    /// to that stat by any of the scrolls in the input set.
+    /// scroll grants a bonus to each stat that is equal to the highest bonus
+    /// granted to that stat by any success of any of the scrolls in the input
+    /// set, and likewise for penalties. Because failing to change a stat at
+    /// all is always possible, the bonuses are never negative, and the
+    /// penalties are never positive.
     ///
-    /// Basically, the `stats` member of the master scroll is generated by
-    /// summing up all of the `stats` members of the elements of the input set
-    /// (`scrolls`), if you think of each `Stats` struct as an element of a
-    /// [max tropical](https://en.wikipedia.org/wiki/Tropical_semiring)
-    /// [semimodule](https://en.wikipedia.org/wiki/Semimodule).
+    /// Basically, the `best` member of the master scroll is generated by
+    /// summing up all of the changes that the elements of the input set
+    /// (`scrolls`) can make, if you think of each `StatDelta` struct as an
+    /// element of a [max
+    /// tropical](https://en.wikipedia.org/wiki/Tropical_semiring)
+    /// [semimodule](https://en.wikipedia.org/wiki/Semimodule), and the
+    /// `worst` member is the same, but for the min tropical semimodule.
     ///
     /// ## Invariants:
     ///
     /// - `!scrolls.is_empty()`
     /// - Every stat array of every scroll in `scrolls` is of equal length.
-    pub fn master_scroll(scrolls: &[Self]) -> Self {
+    pub fn master_scroll(scrolls: &[Self]) -> MasterScroll {
         debug_assert!(!scrolls.is_empty());
+
+        let len = scrolls
+            .iter()
+            .flat_map(|s| s.success.iter())
+            .map(|(_, success)| success.stats.len())
+            .next()
+            .unwrap_or_default();
         debug_assert!(scrolls
             .iter()
-            .map(|s| s.stats.len())
-            .all(|l| l == scrolls[0].stats.len()));
+            .flat_map(|s| s.success.iter())
+            .all(|(_, success)| success.stats.len() == len));
 
-        let mut master = Self::new(
-            1.0,
-            false,
-            f64::INFINITY,
-            Stats::from_vec(vec![0; scrolls[0].stats.len()]),
-        );
+        let mut master = MasterScroll {
+            best: StatDelta::from_vec(vec![0; len]),
+            worst: StatDelta::from_vec(vec![0; len]),
+            clean_slate: scrolls
+                .iter()
+                .any(|s| s.kind() == ScrollKind::CleanSlate),
+            hammer: scrolls.iter().any(|s| s.kind() == ScrollKind::Hammer),
+            resets: scrolls
+                .iter()
+                .filter_map(|s| match &s.effect {
+                    ScrollEffect::Reset { slots, stats } => {
+                        Some((*slots, stats.clone()))
+                    }
+                    _ => None,
+                })
+                .collect(),
+            grants_slots: scrolls.iter().any(|s| {
+                s.success.iter().any(|(_, success)| success.slots >= 0)
+                    || s.miss_slots > 0
+            }),
+            streaks: scrolls.iter().any(|s| s.success_rate.is_some()),
+        };
 
-        for scroll in scrolls {
-            master.stats.max_in_place(&scroll.stats);
+        for (_, success) in scrolls.iter().flat_map(|s| s.success.iter()) {
+            master.best.max_in_place(&success.stats);
+            master.worst.min_in_place(&success.stats);
         }
 
         master
     }
 }
+
+/// The probability of booming the item of a scroll whose probability of
+/// success is `p_suc`, depending on whether or not it's `dark`. A dark scroll
+/// booms the item on half of its failures.
+const fn p_boom_if_dark(p_suc: f64, dark: bool) -> f64 {
+    if dark {
+        (1.0 - p_suc) / 2.0
+    } else {
+        0.0
+    }
+}
+
+/// Returns the total of `costs` (see `Scroll::costs`), at `rates` apiece.
+/// Currencies that `rates` has no rate for are worth one apiece.
+pub(crate) fn exchange(costs: &[f64], rates: &[f64]) -> f64 {
+    costs
+        .iter()
+        .enumerate()
+        .map(|(i, cost)| cost * rates.get(i).copied().unwrap_or(1.0))
+        .sum()
+}
+
+/// Splits `costs` (see `Scroll::costs`) into the cost in currency zero, and
+/// the costs in every other currency.
+fn split_costs(mut costs: Vec<f64>) -> (f64, Vec<f64>) {
+    if costs.is_empty() {
+        return (0.0, costs);
+    }
+    let cost = costs.remove(0);
+
+    (cost, costs)
+}
+
+/// Adds `other` to `costs`, currency by currency. Currencies missing from
+/// either one count as zero.
+fn add_costs(costs: &mut Vec<f64>, other: &[f64]) {
+    if costs.len() < other.len() {
+        costs.resize(other.len(), 0.0);
+    }
+    for (cost, other_cost) in costs.iter_mut().zip(other.iter()) {
+        *cost += other_cost;
+    }
+}
+
+/// The probability of booming the item of a scroll whose probability of
+/// success changes from `p_suc` to `new_p_suc`, if it was `p_boom` before.
+/// The probability of failure changes by the same factor.
+fn scale_p_boom(p_boom: f64, p_suc: f64, new_p_suc: f64) -> f64 {
+    let p_fail = 1.0 - p_suc;
+    if p_fail > 0.0 {
+        p_boom * (1.0 - new_p_suc) / p_fail
+    } else {
+        p_boom
+    }
+}
+
+impl SuccessRate {
+    /// Creates a new success rate from `rate`, which takes the usual
+    /// probability of success of the scroll (`Scroll::p_suc`) and the item
+    /// that the scroll is used on, and returns the actual probability of
+    /// success. Results outside of `[0, 1]` are clamped to it.
+    pub fn new(rate: impl Fn(f64, &ItemContext<'_>) -> f64 + 'static) -> Self {
+        Self(Rc::new(rate))
+    }
+
+    /// Returns the actual probability of success of a scroll whose usual
+    /// probability of success is `p_suc`, when it's used on `item`.
+    pub fn p_suc(&self, p_suc: f64, item: &ItemContext<'_>) -> f64 {
+        (self.0)(p_suc, item).clamp(0.0, 1.0)
+    }
+}
+
+impl fmt::Debug for SuccessRate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SuccessRate(..)")
+    }
+}
+
+/// Two success rates are equal only if they are the very same function.
+impl PartialEq for SuccessRate {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl ScrollFilter {
+    /// Creates a new scroll filter from `filter`, which returns `true` for
+    /// the scrolls that are to be affected.
+    pub fn new(filter: impl Fn(&Scroll) -> bool + 'static) -> Self {
+        Self(Rc::new(filter))
+    }
+
+    /// Returns `true` iff `scroll` is to be affected.
+    pub fn matches(&self, scroll: &Scroll) -> bool {
+        (self.0)(scroll)
+    }
+}
+
+impl fmt::Debug for ScrollFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ScrollFilter(..)")
+    }
+}
+
+/// Two scroll filters are equal only if they are the very same function.
+impl PartialEq for ScrollFilter {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Event {
+    /// Creates a new event that multiplies the probability of success of
+    /// every kind of scroll by `p_suc_factor`, and then adds `p_suc_bonus` to
+    /// it. For an event that only affects some kinds of scroll, set
+    /// `Event::kinds` afterwards, and for one that only affects some scrolls
+    /// of those kinds, see `Event::with_filter`.
+    pub fn new(p_suc_factor: f64, p_suc_bonus: f64) -> Self {
+        Self {
+            p_suc_factor,
+            p_suc_bonus,
+            kinds: ScrollKind::ALL.to_vec(),
+            filter: None,
+        }
+    }
+
+    /// Returns this event, except that it only affects the scrolls for which
+    /// `filter` returns `true` (see `Event::filter`).
+    pub fn with_filter(
+        mut self,
+        filter: impl Fn(&Scroll) -> bool + 'static,
+    ) -> Self {
+        self.filter = Some(ScrollFilter::new(filter));
+
+        self
+    }
+
+    /// Returns `scroll` as it is during this event. Any change to the
+    /// probability of success comes out of (or goes into) the probabilities
+    /// of missing and of booming in proportion to their size. Any modifiers
+    /// that `scroll` is used alongside are counted as part of its probability
+    /// of success, and if the scroll has a `Scroll::success_rate`, that
+    /// applies on top of the event. Whether or not the event affects `scroll`
+    /// at all depends on `scroll` as it is without the event.
+    pub fn scroll(&self, scroll: &Scroll) -> Scroll {
+        let mut scroll = scroll.clone();
+        if self.kinds.contains(&scroll.kind())
+            && self
+                .filter
+                .as_ref()
+                .is_none_or(|filter| filter.matches(&scroll))
+        {
+            let p_suc = (scroll.p_suc * self.p_suc_factor + self.p_suc_bonus)
+                .clamp(0.0, 1.0);
+            scroll.p_boom = scale_p_boom(scroll.p_boom, scroll.p_suc, p_suc);
+            scroll.p_suc = p_suc;
+        }
+
+        scroll
+    }
+
+    /// Returns `scrolls` as they are during this event. See `Event::scroll`.
+    pub fn apply(&self, scrolls: &[Scroll]) -> Vec<Scroll> {
+        scrolls.iter().map(|scroll| self.scroll(scroll)).collect()
+    }
+}
+
+impl Pricing {
+    /// Creates a new pricing, whereby the number of scrolls bought is kept at
+    /// index `purchases` of the item's purchases, and the scrolls are priced
+    /// by `tiers`. See `Pricing::tiers`.
+    pub fn new(purchases: usize, tiers: Vec<(u8, f64)>) -> Self {
+        Self { purchases, tiers }
+    }
+
+    /// Creates a new pricing for a scroll of which `owned` are already owned,
+    /// so that using those costs nothing (other than any modifiers), after
+    /// which any more have to be bought at the usual price.
+    pub fn owned(purchases: usize, owned: u8) -> Self {
+        Self::new(purchases, vec![(owned, 0.0)])
+    }
+
+    /// Returns how many scrolls the tiers cover, in total. Once this many
+    /// have been bought, the price stops changing, so there's no need to
+    /// count any further.
+    pub fn tiered(&self) -> u8 {
+        self.tiers
+            .iter()
+            .fold(0, |total, (count, _)| total.saturating_add(*count))
+    }
+}
+
+impl ScrollKind {
+    /// Every kind of scroll.
+    pub const ALL: [Self; 4] =
+        [Self::Regular, Self::CleanSlate, Self::Hammer, Self::Reset];
+}
+
+impl MasterScroll {
+    /// Returns the least and greatest stats (respectively) that an item with
+    /// stats `stats` could possibly end up with, after its `slots` slots are
+    /// used up, along with its `failed_slots` failed slots, if they can be
+    /// recovered, and the slots that its `hammers` hammers could add, if
+    /// there is a hammer to use. If the item can be reset, then the bounds
+    /// also cover whatever it could end up with after being reset.
+    ///
+    /// These bounds remain correct even though stats are clamped at zero
+    /// after each scroll (see `Stats::plus_delta`), because clamping never
+    /// lowers a stat, and never raises one past what it was before the scroll
+    /// that would have made it negative.
+    pub fn bounds(
+        &self,
+        stats: &Stats,
+        slots: u8,
+        failed_slots: u8,
+        hammers: u8,
+    ) -> (Stats, Stats) {
+        let (mut lo, mut hi) =
+            self.bounds_without_reset(stats, slots, failed_slots, hammers);
+
+        for (clean_slots, clean_stats) in self.resets.iter() {
+            let (reset_lo, reset_hi) = self.bounds_without_reset(
+                clean_stats,
+                *clean_slots,
+                0,
+                hammers,
+            );
+            lo.min_in_place(&reset_lo);
+            hi.max_in_place(&reset_hi);
+        }
+
+        (lo, hi)
+    }
+
+    /// Like `MasterScroll::bounds`, but assuming that the item is never
+    /// reset.
+    fn bounds_without_reset(
+        &self,
+        stats: &Stats,
+        slots: u8,
+        failed_slots: u8,
+        hammers: u8,
+    ) -> (Stats, Stats) {
+        // None of the scrolls change stats at all.
+        if self.best.is_empty() {
+            return (stats.clone(), stats.clone());
+        }
+
+        if self.grants_slots {
+            return (
+                stats.plus_delta(&(self.worst.clone() * i32::from(u16::MAX))),
+                stats.plus_delta(&(self.best.clone() * i32::from(u16::MAX))),
+            );
+        }
+
+        let mut uses = i32::from(slots);
+        if self.clean_slate {
+            uses += i32::from(failed_slots);
+        }
+        if self.hammer {
+            uses += i32::from(hammers);
+        }
+
+        (
+            stats.plus_delta(&(self.worst.clone() * uses)),
+            stats.plus_delta(&(self.best.clone() * uses)),
+        )
+    }
+
+    /// Can an item end up back in a state that it was in before, e.g. by
+    /// having a failed slot recovered, or by being reset?
+    pub fn cyclic(&self) -> bool {
+        self.clean_slate || !self.resets.is_empty() || self.grants_slots
+    }
+}
+
+impl Success {
+    /// Creates a new success that changes the item's stats by `stats`, and
+    /// its slots by `slots`.
+    pub fn new(stats: impl Into<StatDelta>, slots: i8) -> Self {
+        Self {
+            stats: stats.into(),
+            slots,
+        }
+    }
+}
+
+impl From<StatDelta> for Success {
+    /// Creates a new success that changes the item's stats by `stats`, and
+    /// uses up one slot, as most successes do.
+    fn from(stats: StatDelta) -> Self {
+        Self::new(stats, -1)
+    }
+}
+
+impl Modifier {
+    /// Creates a new modifier from its cost (`cost`), how much it adds to the
+    /// probability of success (`p_suc_bonus`), whether or not a failure keeps
+    /// the slot (`keeps_slot`), and whether or not a failure is prevented from
+    /// booming the item (`protects`).
+    pub const fn new(
+        cost: f64,
+        p_suc_bonus: f64,
+        keeps_slot: bool,
+        protects: bool,
+    ) -> Self {
+        Self {
+            cost,
+            other_costs: Vec::new(),
+            p_suc_bonus,
+            keeps_slot,
+            protects,
+        }
+    }
+
+    /// Returns this modifier, except that it costs `costs[i]` in currency
+    /// `i`. See `Scroll::with_costs`.
+    pub fn with_costs(mut self, costs: Vec<f64>) -> Self {
+        let (cost, other_costs) = split_costs(costs);
+        self.cost = cost;
+        self.other_costs = other_costs;
+
+        self
+    }
+
+    /// Returns how much the modifier costs in each currency, starting with
+    /// currency zero. See `Scroll::costs`.
+    pub fn costs(&self) -> Vec<f64> {
+        iter::once(self.cost)
+            .chain(self.other_costs.iter().copied())
+            .collect()
+    }
+
+    /// A White Scroll, which keeps the slot when the scroll fails.
+    pub const fn white_scroll(cost: f64) -> Self {
+        Self::new(cost, 0.0, true, false)
+    }
+
+    /// A Protect Shield, which prevents a failed dark scroll from booming the
+    /// item, turning any boom into a miss.
+    pub const fn protect_shield(cost: f64) -> Self {
+        Self::new(cost, 0.0, false, true)
+    }
+
+    /// A Lucky Day scroll, which adds `p_suc_bonus` to the probability of
+    /// success.
+    pub const fn lucky_day(cost: f64, p_suc_bonus: f64) -> Self {
+        Self::new(cost, p_suc_bonus, false, false)
+    }
+}
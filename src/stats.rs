@@ -3,10 +3,12 @@ use std::{
     fmt,
     hash::{Hash, Hasher},
     ops::Mul,
+    slice,
 };
 
-/// An ordered array of stats that an item can have (q.v. `ItemState`), or that
-/// a scroll can grant on success (q.v. `Scroll`).
+/// An ordered array of stats that an item can have (q.v. `ItemState`). Stats
+/// are never negative; changes to stats, which can be, are represented by
+/// `StatDelta` instead.
 ///
 /// The elements of the stat array are not identified nominally, and can only
 /// be identified by their position/index within the array. As such, obviously,
@@ -40,8 +42,14 @@ impl Stats {
         self.len() == 0
     }
 
+    /// Returns an iterator over the stat array, in order of index.
+    pub fn iter(&self) -> slice::Iter<'_, u16> {
+        self.stats.iter()
+    }
+
     /// Adds `self` to `other`, using ordinary addition, and returns the result
-    /// as a freshly-allocated `Stats`.
+    /// as a freshly-allocated `Stats`. Any stat that would overflow is
+    /// `u16::MAX` instead.
     ///
     /// ## Invariants:
     ///
@@ -54,7 +62,32 @@ impl Stats {
                 .stats
                 .iter()
                 .zip(other.stats.iter())
-                .map(|(s0, s1)| s0 + s1)
+                .map(|(s0, s1)| s0.saturating_add(*s1))
+                .collect(),
+        }
+    }
+
+    /// Changes `self` by `delta`, and returns the result as a
+    /// freshly-allocated `Stats`. As in the game, stats can't go below zero,
+    /// so any stat that `delta` would make negative is zero instead. Likewise,
+    /// any stat that would overflow is `u16::MAX` instead.
+    ///
+    /// ## Invariants:
+    ///
+    /// - `self.len() == delta.len()`
+    pub fn plus_delta(&self, delta: &StatDelta) -> Self {
+        debug_assert_eq!(self.len(), delta.len());
+
+        Self {
+            stats: self
+                .stats
+                .iter()
+                .zip(delta.deltas.iter())
+                .map(|(s, d)| {
+                    (i64::from(*s) + i64::from(*d))
+                        .clamp(0, i64::from(u16::MAX))
+                        as u16
+                })
                 .collect(),
         }
     }
@@ -64,8 +97,6 @@ impl Stats {
     /// [semimodule](https://en.wikipedia.org/wiki/Semimodule), but the result
     /// is simply used to mutate `self` in-place.
     ///
-    /// This function is used by `Scroll::master_scroll`.
-    ///
     /// ## Invariants:
     ///
     /// - `self.len() == other.len()`
@@ -78,6 +109,23 @@ impl Stats {
             }
         }
     }
+
+    /// The dual of `Stats::max_in_place`, i.e. the same thing, but over the
+    /// [min tropical](https://en.wikipedia.org/wiki/Tropical_semiring)
+    /// semimodule instead.
+    ///
+    /// ## Invariants:
+    ///
+    /// - `self.len() == other.len()`
+    pub fn min_in_place(&mut self, other: &Stats) {
+        debug_assert_eq!(self.len(), other.len());
+
+        for (i, stat) in other.stats.iter().enumerate() {
+            if stat < &self.stats[i] {
+                self.stats[i] = *stat;
+            }
+        }
+    }
 }
 
 impl PartialEq for Stats {
@@ -146,9 +194,10 @@ impl Mul<u16> for Stats {
 
     /// Implements [scalar
     /// multiplication](https://en.wikipedia.org/wiki/Scalar_multiplication).
+    /// Any stat that would overflow is `u16::MAX` instead.
     fn mul(mut self, rhs: u16) -> Self::Output {
         for stat in self.stats.iter_mut() {
-            *stat *= rhs;
+            *stat = stat.saturating_mul(rhs);
         }
 
         self
@@ -170,3 +219,90 @@ impl fmt::Display for Stats {
         Ok(())
     }
 }
+
+/// An ordered array of changes to stats, as conferred by a scroll (q.v.
+/// `Scroll`). Unlike the stats themselves, changes to stats can be negative.
+///
+/// The elements of the array are identified by their position/index within
+/// the array, in exactly the same way as for `Stats`, and the same invariant
+/// applies: any two `StatDelta`s (or a `StatDelta` and a `Stats`) that are
+/// used together are assumed to have arrays of equal length.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct StatDelta {
+    deltas: Vec<i32>,
+}
+
+impl StatDelta {
+    /// Creates a new `StatDelta` using the provided array of changes.
+    pub const fn from_vec(deltas: Vec<i32>) -> Self {
+        Self { deltas }
+    }
+
+    /// The length of this `StatDelta`'s array.
+    pub fn len(&self) -> usize {
+        self.deltas.len()
+    }
+
+    /// Returns `self.len() == 0`.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns an iterator over the array of changes, in order of index.
+    pub fn iter(&self) -> slice::Iter<'_, i32> {
+        self.deltas.iter()
+    }
+
+    /// Like `Stats::max_in_place`, but for changes to stats.
+    ///
+    /// ## Invariants:
+    ///
+    /// - `self.len() == other.len()`
+    pub fn max_in_place(&mut self, other: &Self) {
+        debug_assert_eq!(self.len(), other.len());
+
+        for (d0, d1) in self.deltas.iter_mut().zip(other.deltas.iter()) {
+            *d0 = (*d0).max(*d1);
+        }
+    }
+
+    /// The dual of `StatDelta::max_in_place`, i.e. the same thing, but over
+    /// the [min tropical](https://en.wikipedia.org/wiki/Tropical_semiring)
+    /// semimodule instead.
+    ///
+    /// ## Invariants:
+    ///
+    /// - `self.len() == other.len()`
+    pub fn min_in_place(&mut self, other: &Self) {
+        debug_assert_eq!(self.len(), other.len());
+
+        for (d0, d1) in self.deltas.iter_mut().zip(other.deltas.iter()) {
+            *d0 = (*d0).min(*d1);
+        }
+    }
+}
+
+impl From<Stats> for StatDelta {
+    /// Creates a `StatDelta` that increases each stat by the corresponding
+    /// stat of `stats`.
+    fn from(stats: Stats) -> Self {
+        Self {
+            deltas: stats.stats.into_iter().map(i32::from).collect(),
+        }
+    }
+}
+
+impl Mul<i32> for StatDelta {
+    type Output = StatDelta;
+
+    /// Implements [scalar
+    /// multiplication](https://en.wikipedia.org/wiki/Scalar_multiplication).
+    /// Any change that would overflow is `i32::MAX` (or `i32::MIN`) instead.
+    fn mul(mut self, rhs: i32) -> Self::Output {
+        for delta in self.deltas.iter_mut() {
+            *delta = delta.saturating_mul(rhs);
+        }
+
+        self
+    }
+}